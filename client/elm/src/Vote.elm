@@ -339,7 +339,9 @@ update cmd model =
                             Err "chyba sítě"
 
                         Http.BadStatus_ _ body ->
-                            Err body
+                            D.decodeString (D.field "message" D.string) body
+                                |> Result.withDefault body
+                                |> Err
 
                         Http.GoodStatus_ _ _ ->
                             Ok ()
@@ -0,0 +1,39 @@
+-- Moves answers from the per-poll/per-candidate columns (rd2_0 .. emj_9) of the original `votes`
+-- table into the single `polls` column.
+
+BEGIN;
+
+ALTER TABLE votes ADD COLUMN polls jsonb;
+
+UPDATE votes SET polls = jsonb_build_object(
+    'twoRound', coalesce(array_position(ARRAY[rd2_0, rd2_1, rd2_2, rd2_3, rd2_4, rd2_5, rd2_6, rd2_7, rd2_8, rd2_9], 1) - 1, -1),
+    'oneRound', coalesce(array_position(ARRAY[rd1_0, rd1_1, rd1_2, rd1_3, rd1_4, rd1_5, rd1_6, rd1_7, rd1_8, rd1_9], 1) - 1, -1),
+    'divide', jsonb_build_array(div_0, div_1, div_2, div_3, div_4, div_5, div_6, div_7, div_8, div_9),
+    'd21', jsonb_build_array(d21_0, d21_1, d21_2, d21_3, d21_4, d21_5, d21_6, d21_7, d21_8, d21_9),
+    'doodle', jsonb_build_array(ddl_0, ddl_1, ddl_2, ddl_3, ddl_4, ddl_5, ddl_6, ddl_7, ddl_8, ddl_9),
+    'order', jsonb_build_array(ord_0, ord_1, ord_2, ord_3, ord_4, ord_5, ord_6, ord_7, ord_8, ord_9),
+    'star', jsonb_build_array(str_0, str_1, str_2, str_3, str_4, str_5, str_6, str_7, str_8, str_9),
+    'emoji', jsonb_build_array(emj_0, emj_1, emj_2, emj_3, emj_4, emj_5, emj_6, emj_7, emj_8, emj_9)
+);
+
+ALTER TABLE votes ALTER COLUMN polls SET NOT NULL;
+
+ALTER TABLE votes
+    DROP COLUMN rd2_0, DROP COLUMN rd2_1, DROP COLUMN rd2_2, DROP COLUMN rd2_3, DROP COLUMN rd2_4,
+    DROP COLUMN rd2_5, DROP COLUMN rd2_6, DROP COLUMN rd2_7, DROP COLUMN rd2_8, DROP COLUMN rd2_9,
+    DROP COLUMN rd1_0, DROP COLUMN rd1_1, DROP COLUMN rd1_2, DROP COLUMN rd1_3, DROP COLUMN rd1_4,
+    DROP COLUMN rd1_5, DROP COLUMN rd1_6, DROP COLUMN rd1_7, DROP COLUMN rd1_8, DROP COLUMN rd1_9,
+    DROP COLUMN div_0, DROP COLUMN div_1, DROP COLUMN div_2, DROP COLUMN div_3, DROP COLUMN div_4,
+    DROP COLUMN div_5, DROP COLUMN div_6, DROP COLUMN div_7, DROP COLUMN div_8, DROP COLUMN div_9,
+    DROP COLUMN d21_0, DROP COLUMN d21_1, DROP COLUMN d21_2, DROP COLUMN d21_3, DROP COLUMN d21_4,
+    DROP COLUMN d21_5, DROP COLUMN d21_6, DROP COLUMN d21_7, DROP COLUMN d21_8, DROP COLUMN d21_9,
+    DROP COLUMN ddl_0, DROP COLUMN ddl_1, DROP COLUMN ddl_2, DROP COLUMN ddl_3, DROP COLUMN ddl_4,
+    DROP COLUMN ddl_5, DROP COLUMN ddl_6, DROP COLUMN ddl_7, DROP COLUMN ddl_8, DROP COLUMN ddl_9,
+    DROP COLUMN ord_0, DROP COLUMN ord_1, DROP COLUMN ord_2, DROP COLUMN ord_3, DROP COLUMN ord_4,
+    DROP COLUMN ord_5, DROP COLUMN ord_6, DROP COLUMN ord_7, DROP COLUMN ord_8, DROP COLUMN ord_9,
+    DROP COLUMN str_0, DROP COLUMN str_1, DROP COLUMN str_2, DROP COLUMN str_3, DROP COLUMN str_4,
+    DROP COLUMN str_5, DROP COLUMN str_6, DROP COLUMN str_7, DROP COLUMN str_8, DROP COLUMN str_9,
+    DROP COLUMN emj_0, DROP COLUMN emj_1, DROP COLUMN emj_2, DROP COLUMN emj_3, DROP COLUMN emj_4,
+    DROP COLUMN emj_5, DROP COLUMN emj_6, DROP COLUMN emj_7, DROP COLUMN emj_8, DROP COLUMN emj_9;
+
+COMMIT;
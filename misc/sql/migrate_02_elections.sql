@@ -0,0 +1,14 @@
+-- Assigns all existing votes to the default 2023 presidential election. Vote ids are unique
+-- within an election only.
+
+BEGIN;
+
+ALTER TABLE votes ADD COLUMN election_id varchar(64) NOT NULL DEFAULT 'prezident-2023';
+ALTER TABLE votes ALTER COLUMN election_id DROP DEFAULT;
+
+ALTER TABLE votes DROP CONSTRAINT votes_pkey;
+ALTER TABLE votes ADD PRIMARY KEY (election_id, id);
+
+CREATE INDEX votes_election_id_voted ON votes (election_id, voted);
+
+COMMIT;
@@ -0,0 +1,9 @@
+-- Stores times with their time zone, so that comparing them with the voting window does not
+-- depend on the session time zone. Existing values are read in the time zone of the session,
+-- run the migration with the same `TimeZone` setting the server used.
+
+BEGIN;
+
+ALTER TABLE votes ALTER COLUMN voted TYPE timestamptz;
+
+COMMIT;
@@ -0,0 +1,9 @@
+-- Stores the proof of work challenge a vote was computed for, each one can be used only once.
+
+BEGIN;
+
+ALTER TABLE votes ADD COLUMN challenge varchar(128);
+
+CREATE UNIQUE INDEX votes_election_id_challenge ON votes (election_id, challenge);
+
+COMMIT;
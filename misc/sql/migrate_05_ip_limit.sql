@@ -0,0 +1,9 @@
+-- Flags votes over the per IP address limit and speeds up counting votes by IP address.
+
+BEGIN;
+
+ALTER TABLE votes ADD COLUMN flagged boolean DEFAULT false NOT NULL;
+
+CREATE INDEX votes_election_id_ip_hash_voted ON votes (election_id, ip_hash, voted);
+
+COMMIT;
@@ -0,0 +1,8 @@
+-- Records the key of each IP address hash, existing votes were hashed with the legacy salt.
+
+BEGIN;
+
+ALTER TABLE votes ADD COLUMN ip_hash_key_id varchar(32) NOT NULL DEFAULT 'legacy';
+ALTER TABLE votes ALTER COLUMN ip_hash_key_id DROP DEFAULT;
+
+COMMIT;
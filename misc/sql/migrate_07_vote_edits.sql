@@ -0,0 +1,20 @@
+-- Lets voters edit their votes and keeps the previous versions.
+
+BEGIN;
+
+ALTER TABLE votes ADD COLUMN edit_token_hash char(64);
+ALTER TABLE votes ADD COLUMN revision integer DEFAULT 1 NOT NULL;
+ALTER TABLE votes ADD COLUMN edited timestamptz;
+
+CREATE TABLE vote_revisions (
+    election_id varchar(64) NOT NULL,
+    vote_id char(36) NOT NULL,
+    revision integer NOT NULL,
+    permutation varchar(128) NOT NULL,
+    polls jsonb NOT NULL,
+    submitted timestamptz NOT NULL,
+    PRIMARY KEY (election_id, vote_id, revision),
+    FOREIGN KEY (election_id, vote_id) REFERENCES votes (election_id, id)
+);
+
+COMMIT;
@@ -0,0 +1,34 @@
+-- Tamper-evident log of stored ballots. Votes stored before the migration are not in the log.
+
+BEGIN;
+
+-- Append-only log of stored ballots per election, each entry hashes the previous one:
+-- entry_hash = sha256(previous_hash || leaf_hash), leaf_hash = sha256(JSON of the vote).
+CREATE TABLE ballot_log (
+    seq bigserial PRIMARY KEY,
+    election_id varchar(64) NOT NULL,
+    vote_id char(36) NOT NULL,
+    revision integer NOT NULL,
+    leaf_hash char(64) NOT NULL,
+    previous_hash char(64) NOT NULL,
+    entry_hash char(64) NOT NULL,
+    logged timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (election_id, vote_id) REFERENCES votes (election_id, id)
+);
+
+CREATE INDEX ballot_log_election_id_seq ON ballot_log (election_id, seq);
+CREATE INDEX ballot_log_election_id_vote_id ON ballot_log (election_id, vote_id);
+
+-- Entries can only be appended, also by the owner of the table.
+CREATE FUNCTION ballot_log_append_only() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'ballot_log is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER ballot_log_no_update_delete BEFORE UPDATE OR DELETE ON ballot_log
+    FOR EACH ROW EXECUTE FUNCTION ballot_log_append_only();
+CREATE TRIGGER ballot_log_no_truncate BEFORE TRUNCATE ON ballot_log
+    FOR EACH STATEMENT EXECUTE FUNCTION ballot_log_append_only();
+
+COMMIT;
@@ -0,0 +1,5 @@
+INSERT INTO ballot_log (
+    election_id, vote_id, revision, leaf_hash, previous_hash, entry_hash
+) values (
+    $1, $2, $3, $4, $5, $6
+);
@@ -0,0 +1,9 @@
+INSERT INTO vote_revisions (
+    election_id, vote_id, revision, permutation, polls, submitted
+)
+SELECT
+    election_id, id, revision, permutation, polls, COALESCE(edited, voted)
+FROM
+    votes
+WHERE
+    id = $1 AND election_id = $2;
@@ -0,0 +1,6 @@
+SELECT
+    count(*) AS count
+FROM
+    votes
+WHERE
+    election_id = $1 AND ip_hash = ANY ($2) AND voted >= $3::timestamptz;
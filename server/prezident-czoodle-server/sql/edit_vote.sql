@@ -0,0 +1,6 @@
+UPDATE
+    votes
+SET
+    permutation = $2, polls = $3, revision = revision + 1, edited = CURRENT_TIMESTAMP
+WHERE
+    id = $1 AND election_id = $4;
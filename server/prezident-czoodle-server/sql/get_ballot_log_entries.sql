@@ -0,0 +1,8 @@
+SELECT
+    seq, revision, leaf_hash, previous_hash, entry_hash
+FROM
+    ballot_log
+WHERE
+    election_id = $1 AND vote_id = $2
+ORDER BY
+    seq;
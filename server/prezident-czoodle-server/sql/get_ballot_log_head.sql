@@ -0,0 +1,7 @@
+SELECT
+    count(*) AS length,
+    (SELECT entry_hash FROM ballot_log WHERE election_id = $1 ORDER BY seq DESC LIMIT 1) AS head_hash
+FROM
+    ballot_log
+WHERE
+    election_id = $1;
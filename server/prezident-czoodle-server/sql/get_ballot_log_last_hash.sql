@@ -0,0 +1,9 @@
+SELECT
+    entry_hash
+FROM
+    ballot_log
+WHERE
+    election_id = $1
+ORDER BY
+    seq DESC
+LIMIT 1;
@@ -0,0 +1,8 @@
+SELECT
+    leaf_hash
+FROM
+    ballot_log
+WHERE
+    election_id = $1 AND seq > $2
+ORDER BY
+    seq;
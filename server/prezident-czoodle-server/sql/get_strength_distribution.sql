@@ -0,0 +1,12 @@
+SELECT
+    strength, count(*) AS count
+FROM
+    votes
+WHERE
+    election_id = $1
+    AND ($2::timestamptz IS NULL OR voted >= $2)
+    AND ($3::timestamptz IS NULL OR voted < $3)
+GROUP BY
+    strength
+ORDER BY
+    strength;
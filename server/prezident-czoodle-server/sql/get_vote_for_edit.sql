@@ -0,0 +1,7 @@
+SELECT
+    edit_token_hash
+FROM
+    votes
+WHERE
+    id = $1 AND election_id = $2
+FOR UPDATE;
@@ -0,0 +1,15 @@
+SELECT
+    revision, permutation, polls, COALESCE(edited, voted) AS submitted
+FROM
+    votes
+WHERE
+    id = $1 AND election_id = $2
+UNION ALL
+SELECT
+    r.revision, r.permutation, r.polls, r.submitted
+FROM
+    vote_revisions r JOIN votes v ON v.election_id = r.election_id AND v.id = r.vote_id
+WHERE
+    v.id = $1 AND v.election_id = $2
+ORDER BY
+    revision;
@@ -0,0 +1,213 @@
+use crate::crypto_utils::{hmac_sha256, random_hex, verify_hmac_sha256};
+use crate::errors::MyError;
+use crate::models::{ChallengeWeb, ElectionWeb};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Issues signed, expiring proof of work challenges and remembers the redeemed ones, so that
+/// votes cannot be precomputed offline and a challenge cannot be used for more than one vote.
+///
+/// A challenge has the form `{expires}.{random}.{signature}`, where the signature covers the
+/// election id, the expiration (unix seconds) and the random part.
+///
+/// The redeemed challenges kept here only reject reused ones early, the unique index on
+/// `votes (election_id, challenge)` is what guarantees single use across restarts.
+#[derive(Debug)]
+pub struct ChallengeStore {
+    secret: Vec<u8>,
+    redeemed: Mutex<HashMap<String, DateTime<Utc>>>,
+}
+
+impl ChallengeStore {
+    /// Uses the configured secret, or a random one when not set (challenges issued before
+    /// a restart are then no longer accepted).
+    pub fn new(secret: Option<&str>) -> Self {
+        let secret = match secret {
+            Some(secret) => secret.as_bytes().to_vec(),
+            None => random_hex(32).into_bytes(),
+        };
+        ChallengeStore {
+            secret,
+            redeemed: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn issue(&self, election: &ElectionWeb, ttl_seconds: u64) -> ChallengeWeb {
+        let expires = Utc::now() + Duration::seconds(ttl_seconds as i64);
+        let payload = format!("{}.{}", expires.timestamp(), random_hex(16));
+        let signature = hmac_sha256(&self.secret, &format!("{}.{}", election.id, payload));
+        ChallengeWeb {
+            election_id: election.id.clone(),
+            challenge: format!("{}.{}", payload, signature),
+            expires,
+        }
+    }
+
+    /// Checks the signature and expiration of the challenge and that it has not been redeemed.
+    pub fn check(&self, election: &ElectionWeb, challenge: &str) -> Result<(), MyError> {
+        self.check_at(election, challenge, Utc::now())
+    }
+
+    fn check_at(
+        &self,
+        election: &ElectionWeb,
+        challenge: &str,
+        now: DateTime<Utc>,
+    ) -> Result<(), MyError> {
+        let invalid = || MyError::invalid("invalid_challenge", "challenge", "Invalid challenge.");
+
+        let (payload, signature) = challenge.rsplit_once('.').ok_or_else(invalid)?;
+        let message = format!("{}.{}", election.id, payload);
+        if !verify_hmac_sha256(&self.secret, &message, signature) {
+            return Result::Err(invalid());
+        }
+        let expires = expiration(challenge).ok_or_else(invalid)?;
+
+        if expires <= now {
+            return Result::Err(MyError::invalid(
+                "challenge_expired",
+                "challenge",
+                "Challenge expired.",
+            ));
+        }
+
+        let mut redeemed = self.redeemed.lock().unwrap();
+        redeemed.retain(|_, &mut expires| expires > now);
+        if redeemed.contains_key(challenge) {
+            return Result::Err(challenge_used());
+        }
+        Result::Ok(())
+    }
+
+    /// Remembers a checked challenge once the vote computed for it is stored.
+    pub fn redeem(&self, challenge: &str) {
+        if let Some(expires) = expiration(challenge) {
+            let mut redeemed = self.redeemed.lock().unwrap();
+            redeemed.insert(challenge.to_owned(), expires);
+        }
+    }
+}
+
+fn expiration(challenge: &str) -> Option<DateTime<Utc>> {
+    challenge
+        .split_once('.')
+        .and_then(|(expires, _)| expires.parse::<i64>().ok())
+        .and_then(|expires| Utc.timestamp_opt(expires, 0).single())
+}
+
+pub fn challenge_used() -> MyError {
+    MyError::invalid("challenge_used", "challenge", "Challenge already used.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn election(id: &str) -> ElectionWeb {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "title": id,
+            "candidates": [],
+        }))
+        .unwrap()
+    }
+
+    fn error_code(result: Result<(), MyError>) -> Option<&'static str> {
+        match result {
+            Result::Ok(()) => None,
+            Result::Err(MyError::ValidationError(invalid)) => Some(invalid.code),
+            Result::Err(err) => panic!("unexpected error {:?}", err),
+        }
+    }
+
+    #[test]
+    fn issued_challenge_is_accepted() {
+        let store = ChallengeStore::new(None);
+        let election = election("test");
+        let challenge = store.issue(&election, 60).challenge;
+
+        assert_eq!(error_code(store.check(&election, &challenge)), None);
+    }
+
+    #[test]
+    fn challenge_of_another_election_is_rejected() {
+        let store = ChallengeStore::new(None);
+        let challenge = store.issue(&election("other"), 60).challenge;
+
+        assert_eq!(
+            error_code(store.check(&election("test"), &challenge)),
+            Some("invalid_challenge")
+        );
+    }
+
+    #[test]
+    fn tampered_challenge_is_rejected() {
+        let store = ChallengeStore::new(None);
+        let election = election("test");
+        let challenge = store.issue(&election, 60).challenge;
+        let (payload, signature) = challenge.rsplit_once('.').unwrap();
+        let (expires, random) = payload.split_once('.').unwrap();
+
+        let flipped = if signature.starts_with('0') { "1" } else { "0" };
+        let tampered = [
+            format!("{}.{}{}", payload, flipped, &signature[1..]),
+            format!("{}.{}0.{}", expires, random, signature),
+            format!(
+                "{}.{}.{}",
+                expires.parse::<i64>().unwrap() + 3600,
+                random,
+                signature
+            ),
+            payload.to_owned(),
+        ];
+        for challenge in tampered {
+            assert_eq!(
+                error_code(store.check(&election, &challenge)),
+                Some("invalid_challenge"),
+                "{}",
+                challenge
+            );
+        }
+    }
+
+    #[test]
+    fn expired_challenge_is_rejected() {
+        let store = ChallengeStore::new(None);
+        let election = election("test");
+        let challenge = store.issue(&election, 60).challenge;
+        let expires = expiration(&challenge).unwrap();
+
+        assert_eq!(
+            error_code(store.check_at(&election, &challenge, expires)),
+            Some("challenge_expired")
+        );
+    }
+
+    #[test]
+    fn redeemed_challenge_is_rejected_until_it_expires() {
+        let store = ChallengeStore::new(None);
+        let election = election("test");
+        let challenge = store.issue(&election, 60).challenge;
+        let expires = expiration(&challenge).unwrap();
+        store.redeem(&challenge);
+
+        assert_eq!(
+            error_code(store.check(&election, &challenge)),
+            Some("challenge_used")
+        );
+        assert_eq!(
+            error_code(store.check_at(&election, &challenge, expires - Duration::seconds(1))),
+            Some("challenge_used")
+        );
+        assert_eq!(
+            error_code(store.check_at(&election, &challenge, expires)),
+            Some("challenge_expired")
+        );
+
+        // Checking a later challenge forgets the expired ones.
+        let later = store.issue(&election, 120).challenge;
+        assert_eq!(error_code(store.check_at(&election, &later, expires)), None);
+        assert!(store.redeemed.lock().unwrap().is_empty());
+    }
+}
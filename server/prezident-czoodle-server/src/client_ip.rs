@@ -0,0 +1,225 @@
+use actix_web::HttpRequest;
+use ipnet::IpNet;
+use serde::Deserialize;
+use std::net::{IpAddr, SocketAddr};
+
+/// Header a trusted reverse proxy passes the client address in.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClientIpHeader {
+    #[default]
+    XRealIp,
+    XForwardedFor,
+    Forwarded,
+}
+
+/// Resolves the address of the client, trusting the proxy header only when the request comes
+/// from a trusted proxy.
+#[derive(Debug, Clone)]
+pub struct ClientIpResolver {
+    trusted_proxies: Vec<IpNet>,
+    header: ClientIpHeader,
+}
+
+impl ClientIpResolver {
+    pub fn new(trusted_proxies: &[String], header: ClientIpHeader) -> Result<Self, String> {
+        let trusted_proxies = trusted_proxies
+            .iter()
+            .map(|cidr| {
+                cidr.parse::<IpNet>()
+                    .or_else(|_| cidr.parse::<IpAddr>().map(IpNet::from))
+                    .map_err(|_| format!("Invalid trusted proxy '{}'.", cidr))
+            })
+            .collect::<Result<Vec<IpNet>, String>>()?;
+        Result::Ok(ClientIpResolver {
+            trusted_proxies,
+            header,
+        })
+    }
+
+    fn is_trusted(&self, ip: &IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|net| net.contains(ip))
+    }
+
+    pub fn resolve(&self, req: &HttpRequest) -> Option<IpAddr> {
+        let peer = req.peer_addr()?.ip();
+        if !self.is_trusted(&peer) {
+            return Some(peer);
+        }
+        let header_ips: Vec<IpAddr> = match self.header {
+            ClientIpHeader::XRealIp => header_values(req, "x-real-ip")
+                .last()
+                .and_then(|value| parse_ip(value))
+                .into_iter()
+                .collect(),
+            ClientIpHeader::XForwardedFor => header_values(req, "x-forwarded-for")
+                .iter()
+                .flat_map(|value| value.split(','))
+                .filter_map(parse_ip)
+                .collect(),
+            ClientIpHeader::Forwarded => header_values(req, "forwarded")
+                .iter()
+                .flat_map(|value| value.split(','))
+                .filter_map(forwarded_for)
+                .collect(),
+        };
+        // Proxies append the address they got the request from, so the client is the last
+        // address that is not a trusted proxy.
+        header_ips
+            .iter()
+            .rev()
+            .find(|ip| !self.is_trusted(ip))
+            .or_else(|| header_ips.first())
+            .copied()
+            .or(Some(peer))
+    }
+}
+
+fn header_values<'a>(req: &'a HttpRequest, name: &str) -> Vec<&'a str> {
+    req.headers()
+        .get_all(name)
+        .filter_map(|value| value.to_str().ok())
+        .collect()
+}
+
+/// Address of the `for` parameter of one `Forwarded` element, e.g. `for="[2001:db8::1]:4711"`.
+fn forwarded_for(element: &str) -> Option<IpAddr> {
+    element
+        .split(';')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("for"))
+        .and_then(|(_, value)| parse_ip(value))
+}
+
+/// Parses an address optionally quoted, in brackets or with a port.
+fn parse_ip(value: &str) -> Option<IpAddr> {
+    let value = value.trim().trim_matches('"');
+    if let Ok(ip) = value.parse::<IpAddr>() {
+        return Some(ip);
+    }
+    if let Ok(socket) = value.parse::<SocketAddr>() {
+        return Some(socket.ip());
+    }
+    value
+        .strip_prefix('[')
+        .and_then(|rest| rest.split_once(']'))
+        .and_then(|(ip, _)| ip.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    fn resolver(header: ClientIpHeader) -> ClientIpResolver {
+        let trusted = ["127.0.0.1".to_owned(), "10.0.0.0/8".to_owned()];
+        ClientIpResolver::new(&trusted, header).unwrap()
+    }
+
+    fn resolve(header: ClientIpHeader, peer: &str, headers: &[(&str, &str)]) -> Option<IpAddr> {
+        let mut req = TestRequest::default().peer_addr(peer.parse().unwrap());
+        for &(name, value) in headers {
+            req = req.append_header((name, value));
+        }
+        resolver(header).resolve(&req.to_http_request())
+    }
+
+    fn ip(value: &str) -> Option<IpAddr> {
+        Some(value.parse().unwrap())
+    }
+
+    #[test]
+    fn untrusted_peer_cannot_set_the_address() {
+        let headers = [("x-real-ip", "203.0.113.7")];
+        assert_eq!(
+            resolve(ClientIpHeader::XRealIp, "198.51.100.1:4000", &headers),
+            ip("198.51.100.1")
+        );
+        let headers = [("x-forwarded-for", "203.0.113.7")];
+        assert_eq!(
+            resolve(ClientIpHeader::XForwardedFor, "198.51.100.1:4000", &headers),
+            ip("198.51.100.1")
+        );
+    }
+
+    #[test]
+    fn trusted_peer_passes_the_real_ip() {
+        let headers = [("x-real-ip", "203.0.113.7")];
+        assert_eq!(
+            resolve(ClientIpHeader::XRealIp, "127.0.0.1:4000", &headers),
+            ip("203.0.113.7")
+        );
+    }
+
+    #[test]
+    fn forwarded_for_chain_takes_the_last_untrusted_address() {
+        // The client claims 192.0.2.1, the first proxy saw 203.0.113.7 and the second 10.0.0.2.
+        let headers = [("x-forwarded-for", "192.0.2.1, 203.0.113.7, 10.0.0.2")];
+        assert_eq!(
+            resolve(ClientIpHeader::XForwardedFor, "10.0.0.1:4000", &headers),
+            ip("203.0.113.7")
+        );
+        let headers = [
+            ("x-forwarded-for", "192.0.2.1"),
+            ("x-forwarded-for", "203.0.113.7, 10.0.0.2"),
+        ];
+        assert_eq!(
+            resolve(ClientIpHeader::XForwardedFor, "10.0.0.1:4000", &headers),
+            ip("203.0.113.7")
+        );
+    }
+
+    #[test]
+    fn forwarded_header_with_ipv6_and_port() {
+        let headers = [(
+            "forwarded",
+            "for=192.0.2.1;proto=https, For=\"[2001:db8::1]:4711\";by=10.0.0.2",
+        )];
+        assert_eq!(
+            resolve(ClientIpHeader::Forwarded, "10.0.0.1:4000", &headers),
+            ip("2001:db8::1")
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_peer_address() {
+        assert_eq!(
+            resolve(ClientIpHeader::XRealIp, "127.0.0.1:4000", &[]),
+            ip("127.0.0.1")
+        );
+        let headers = [("x-real-ip", "unknown")];
+        assert_eq!(
+            resolve(ClientIpHeader::XRealIp, "127.0.0.1:4000", &headers),
+            ip("127.0.0.1")
+        );
+        // A header of a different proxy is ignored.
+        let headers = [("x-forwarded-for", "203.0.113.7")];
+        assert_eq!(
+            resolve(ClientIpHeader::XRealIp, "127.0.0.1:4000", &headers),
+            ip("127.0.0.1")
+        );
+        // Only trusted addresses in the chain, the client is a proxy itself.
+        let headers = [("x-forwarded-for", "10.0.0.3, 10.0.0.2")];
+        assert_eq!(
+            resolve(ClientIpHeader::XForwardedFor, "10.0.0.1:4000", &headers),
+            ip("10.0.0.3")
+        );
+    }
+
+    #[test]
+    fn parse_ip_accepts_ports_quotes_and_brackets() {
+        assert_eq!(parse_ip(" 203.0.113.7 "), ip("203.0.113.7"));
+        assert_eq!(parse_ip("203.0.113.7:80"), ip("203.0.113.7"));
+        assert_eq!(parse_ip("\"[2001:db8::1]:4711\""), ip("2001:db8::1"));
+        assert_eq!(parse_ip("[2001:db8::1]"), ip("2001:db8::1"));
+        assert_eq!(parse_ip("2001:db8::1"), ip("2001:db8::1"));
+        assert_eq!(parse_ip("_hidden"), None);
+        assert_eq!(forwarded_for("proto=https;by=10.0.0.2"), None);
+    }
+
+    #[test]
+    fn invalid_trusted_proxy_is_rejected() {
+        let trusted = ["10.0.0.0/33".to_owned()];
+        assert!(ClientIpResolver::new(&trusted, ClientIpHeader::XRealIp).is_err());
+    }
+}
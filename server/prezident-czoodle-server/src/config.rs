@@ -1,8 +1,157 @@
+use crate::client_ip::ClientIpHeader;
+use crate::models::{all_poll_types, CandidateWeb, ElectionWeb, ProofOfWorkWeb};
+use chrono::{TimeZone, Utc};
 use serde::Deserialize;
 
 #[derive(Debug, Default, Deserialize)]
 pub struct ExampleConfig {
     pub server_addr: String,
-    pub hash_salt: String,
+    /// Salt of IP address hashes from before keyed hashing, still recognized by the IP limit.
+    #[serde(default)]
+    pub hash_salt: Option<String>,
+    /// JSON file with the IP hash keys, managed by the `rotate-ip-hash-keys` command.
+    #[serde(default = "default_ip_hash_keys_file")]
+    pub ip_hash_keys_file: String,
+    /// Addresses or CIDR ranges of reverse proxies whose client address header is trusted.
+    #[serde(default = "default_trusted_proxies")]
+    pub trusted_proxies: Vec<String>,
+    #[serde(default)]
+    pub client_ip_header: ClientIpHeader,
+    /// Key signing proof of work challenges, random on every start if not set.
+    #[serde(default)]
+    pub challenge_secret: Option<String>,
+    /// Key signing vote receipts, random on every start if not set.
+    #[serde(default)]
+    pub receipt_secret: Option<String>,
+    /// Bearer token of the admin endpoints, which are disabled if not set.
+    #[serde(default)]
+    pub admin_token: Option<String>,
     pub pg: deadpool_postgres::Config,
+    /// Elections hosted by the server, the first one is the default for legacy routes. Can be set
+    /// in a `config.toml` (or `config.json`) file, defaults to the 2023 Czech presidential election.
+    #[serde(default = "default_elections")]
+    pub elections: Vec<ElectionWeb>,
+}
+
+impl ExampleConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.elections.is_empty() {
+            return Result::Err("At least one election is required.".to_owned());
+        }
+        for (index, election) in self.elections.iter().enumerate() {
+            if self.elections[..index].iter().any(|e| e.id == election.id) {
+                return Result::Err(format!("Duplicate election id '{}'.", election.id));
+            }
+            validate_candidates(election)?;
+            validate_proof_of_work(election)?;
+            if let Some(ip_limit) = &election.ip_limit {
+                if ip_limit.max_votes < 1 || ip_limit.window_seconds == 0 {
+                    return Result::Err(format!(
+                        "Election '{}' has an invalid IP limit.",
+                        election.id
+                    ));
+                }
+            }
+            if let (Some(opens), Some(closes)) = (election.opens, election.closes) {
+                if opens >= closes {
+                    return Result::Err(format!(
+                        "Election '{}' closes before it opens.",
+                        election.id
+                    ));
+                }
+            }
+        }
+        Result::Ok(())
+    }
+}
+
+fn validate_candidates(election: &ElectionWeb) -> Result<(), String> {
+    if election.candidates.len() < 2 {
+        return Result::Err(format!(
+            "Election '{}' needs at least two candidates.",
+            election.id
+        ));
+    }
+    for (index, candidate) in election.candidates.iter().enumerate() {
+        if candidate.id != index as i32 {
+            return Result::Err(format!(
+                "Candidate '{}' of election '{}' has id {}, expected {}.",
+                candidate.code, election.id, candidate.id, index
+            ));
+        }
+    }
+    Result::Ok(())
+}
+
+fn default_ip_hash_keys_file() -> String {
+    "ip_hash_keys.json".to_owned()
+}
+
+fn default_trusted_proxies() -> Vec<String> {
+    vec!["127.0.0.1/32".to_owned(), "::1/128".to_owned()]
+}
+
+fn default_elections() -> Vec<ElectionWeb> {
+    let candidates = [
+        ("ab", "Andrej Babiš"),
+        ("jb", "Jaroslav Bašta"),
+        ("kd", "Karel Diviš"),
+        ("pf", "Pavel Fischer"),
+        ("mh", "Marek Hilšer"),
+        ("kj", "Karel Janeček"),
+        ("dn", "Danuše Nerudová"),
+        ("pp", "Petr Pavel"),
+        ("js", "Josef Středula"),
+        ("tz", "Tomáš Zima"),
+    ]
+    .iter()
+    .enumerate()
+    .map(|(id, &(code, name))| CandidateWeb {
+        id: id as i32,
+        name: name.to_owned(),
+        code: code.to_owned(),
+    })
+    .collect();
+
+    vec![ElectionWeb {
+        id: "prezident-2023".to_owned(),
+        title: "Prezidentské volby 2023".to_owned(),
+        candidates,
+        polls: all_poll_types(),
+        opens: None,
+        // The first round of the election closed on 2023-01-14 at 14:00 (CET).
+        closes: Utc.with_ymd_and_hms(2023, 1, 14, 13, 0, 0).single(),
+        proof_of_work: ProofOfWorkWeb::default(),
+        ip_limit: None,
+    }]
+}
+
+fn validate_proof_of_work(election: &ElectionWeb) -> Result<(), String> {
+    let pow = &election.proof_of_work;
+    if pow.prefix.is_empty()
+        || !pow
+            .prefix
+            .chars()
+            .all(|c| matches!(c, '0'..='9' | 'a'..='f'))
+    {
+        return Result::Err(format!(
+            "Election '{}' has an invalid proof of work prefix.",
+            election.id
+        ));
+    }
+    if pow.challenge_ttl_seconds == Some(0) {
+        return Result::Err(format!(
+            "Election '{}' has an invalid challenge lifetime.",
+            election.id
+        ));
+    }
+    if let Some(adaptive) = &pow.adaptive {
+        if adaptive.window_seconds == 0 || adaptive.submissions_per_window == 0 {
+            return Result::Err(format!(
+                "Election '{}' has an invalid adaptive difficulty.",
+                election.id
+            ));
+        }
+    }
+    Result::Ok(())
 }
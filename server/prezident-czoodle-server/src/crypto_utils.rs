@@ -1,5 +1,7 @@
 extern crate sha2;
 
+use hmac::{Hmac, Mac};
+use rand::RngCore;
 use sha2::{Digest, Sha256};
 
 pub fn sha256(str: &str) -> String {
@@ -11,3 +13,37 @@ pub fn sha256(str: &str) -> String {
     let strings: Vec<String> = result.iter().map(|b| format!("{:02x}", b)).collect();
     strings.join("")
 }
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub fn hmac_sha256(key: &[u8], message: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Checks a hex encoded HMAC-SHA256 signature in constant time.
+pub fn verify_hmac_sha256(key: &[u8], message: &str, signature: &str) -> bool {
+    let signature = match hex::decode(signature) {
+        Ok(signature) => signature,
+        Err(_) => return false,
+    };
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message.as_bytes());
+    mac.verify_slice(&signature).is_ok()
+}
+
+pub fn random_hex(bytes: usize) -> String {
+    let mut buffer = vec![0u8; bytes];
+    rand::thread_rng().fill_bytes(&mut buffer);
+    hex::encode(buffer)
+}
+
+/// Compares two strings in time depending only on their lengths.
+pub fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
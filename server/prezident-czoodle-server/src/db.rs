@@ -1,144 +1,357 @@
 use crate::{
+    challenge,
+    crypto_utils::{constant_time_eq, sha256},
     errors::MyError,
-    models::{PollsWeb, VoteDB, VoteWeb},
+    ip_hash::IpHash,
+    models::{
+        BallotLogEntryWeb, BallotLogHeadWeb, EditVoteWeb, ElectionWeb, InclusionProofWeb,
+        IpLimitAction, IpLimitWeb, PollsWeb, StrengthCountWeb, VoteDB, VotePollsDB, VoteRange,
+        VoteRevisionDB, VoteRevisionWeb, VoteWeb,
+    },
 };
-use deadpool_postgres::Client;
+use chrono::Utc;
+use deadpool_postgres::{Client, Transaction};
 use tokio_pg_mapper::FromTokioPostgresRow;
+use tokio_postgres::error::SqlState;
+use tokio_postgres::types::Json;
+use tokio_postgres::IsolationLevel;
 
-fn index_to_points(value: i32, index: i32) -> i32 {
-    if index == value {
-        1
-    } else {
-        0
-    }
-}
+/// Unique index making each proof of work challenge usable for one vote only.
+const VOTES_CHALLENGE_INDEX: &str = "votes_election_id_challenge";
 
+/// Stores a new vote. `ip_address_hashes` are the hashes of the voter's address under all
+/// active keys, counted against the IP limit of the election.
 pub async fn add_vote(
-    client: &Client,
+    client: &mut Client,
+    election: &ElectionWeb,
     vote_info: VoteWeb,
-    ip_address_hash: &str,
+    ip_address_hash: &IpHash,
+    ip_address_hashes: &[IpHash],
+    edit_token_hash: &str,
 ) -> Result<(), MyError> {
+    let election_id = election.id.as_str();
+    let transaction = client.transaction().await.map_err(MyError::PGError)?;
+
+    let flagged = match &election.ip_limit {
+        Some(ip_limit) => {
+            check_ip_limit(
+                &transaction,
+                election_id,
+                ip_limit,
+                ip_address_hash,
+                ip_address_hashes,
+            )
+            .await?
+        }
+        None => false,
+    };
+
     let _stmt = include_str!("../sql/add_vote.sql");
-    let stmt = client.prepare(&_stmt).await.unwrap();
+    let stmt = transaction.prepare(_stmt).await.unwrap();
 
-    let order_as_strings: Vec<String> = vote_info.order.iter().map(|&v| v.to_string()).collect();
-    let permutation: String = order_as_strings.join(",");
+    let permutation: String = join_permutation(&vote_info.order);
 
     let nonces_as_one_string = vote_info.nonces.join(",");
 
-    let result = client
+    let result = transaction
         .query(
             &stmt,
             &[
                 &vote_info.uuid,
+                &election_id,
                 &(vote_info.nonces.len() as i32),
                 &nonces_as_one_string,
+                &vote_info.challenge,
                 &permutation,
-                &ip_address_hash,
-                // Two-Round Poll
-                &(index_to_points(vote_info.polls.two_round, 0)),
-                &(index_to_points(vote_info.polls.two_round, 1)),
-                &(index_to_points(vote_info.polls.two_round, 2)),
-                &(index_to_points(vote_info.polls.two_round, 3)),
-                &(index_to_points(vote_info.polls.two_round, 4)),
-                &(index_to_points(vote_info.polls.two_round, 5)),
-                &(index_to_points(vote_info.polls.two_round, 6)),
-                &(index_to_points(vote_info.polls.two_round, 7)),
-                &(index_to_points(vote_info.polls.two_round, 8)),
-                &(index_to_points(vote_info.polls.two_round, 9)),
-                // One-Round Poll
-                &(index_to_points(vote_info.polls.one_round, 0)),
-                &(index_to_points(vote_info.polls.one_round, 1)),
-                &(index_to_points(vote_info.polls.one_round, 2)),
-                &(index_to_points(vote_info.polls.one_round, 3)),
-                &(index_to_points(vote_info.polls.one_round, 4)),
-                &(index_to_points(vote_info.polls.one_round, 5)),
-                &(index_to_points(vote_info.polls.one_round, 6)),
-                &(index_to_points(vote_info.polls.one_round, 7)),
-                &(index_to_points(vote_info.polls.one_round, 8)),
-                &(index_to_points(vote_info.polls.one_round, 9)),
-                // Divide Poll
-                &vote_info.polls.divide[0],
-                &vote_info.polls.divide[1],
-                &vote_info.polls.divide[2],
-                &vote_info.polls.divide[3],
-                &vote_info.polls.divide[4],
-                &vote_info.polls.divide[5],
-                &vote_info.polls.divide[6],
-                &vote_info.polls.divide[7],
-                &vote_info.polls.divide[8],
-                &vote_info.polls.divide[9],
-                // D21 Poll
-                &vote_info.polls.d21[0],
-                &vote_info.polls.d21[1],
-                &vote_info.polls.d21[2],
-                &vote_info.polls.d21[3],
-                &vote_info.polls.d21[4],
-                &vote_info.polls.d21[5],
-                &vote_info.polls.d21[6],
-                &vote_info.polls.d21[7],
-                &vote_info.polls.d21[8],
-                &vote_info.polls.d21[9],
-                // Doodle Poll
-                &vote_info.polls.doodle[0],
-                &vote_info.polls.doodle[1],
-                &vote_info.polls.doodle[2],
-                &vote_info.polls.doodle[3],
-                &vote_info.polls.doodle[4],
-                &vote_info.polls.doodle[5],
-                &vote_info.polls.doodle[6],
-                &vote_info.polls.doodle[7],
-                &vote_info.polls.doodle[8],
-                &vote_info.polls.doodle[9],
-                // Order Poll
-                &vote_info.polls.order[0],
-                &vote_info.polls.order[1],
-                &vote_info.polls.order[2],
-                &vote_info.polls.order[3],
-                &vote_info.polls.order[4],
-                &vote_info.polls.order[5],
-                &vote_info.polls.order[6],
-                &vote_info.polls.order[7],
-                &vote_info.polls.order[8],
-                &vote_info.polls.order[9],
-                // Star Poll
-                &vote_info.polls.star[0],
-                &vote_info.polls.star[1],
-                &vote_info.polls.star[2],
-                &vote_info.polls.star[3],
-                &vote_info.polls.star[4],
-                &vote_info.polls.star[5],
-                &vote_info.polls.star[6],
-                &vote_info.polls.star[7],
-                &vote_info.polls.star[8],
-                &vote_info.polls.star[9],
-                // Emoji Poll
-                &vote_info.polls.emoji[0],
-                &vote_info.polls.emoji[1],
-                &vote_info.polls.emoji[2],
-                &vote_info.polls.emoji[3],
-                &vote_info.polls.emoji[4],
-                &vote_info.polls.emoji[5],
-                &vote_info.polls.emoji[6],
-                &vote_info.polls.emoji[7],
-                &vote_info.polls.emoji[8],
-                &vote_info.polls.emoji[9],
+                &ip_address_hash.hash,
+                &ip_address_hash.key_id,
+                &Json(&vote_info.polls),
+                &flagged,
+                &edit_token_hash,
             ],
         )
         .await;
 
     match result {
-        Ok(_) => Result::Ok(()),
-        Err(err) => Result::Err(MyError::PGError(err)),
+        Ok(_) => {}
+        Err(err) if err.code() == Some(&SqlState::UNIQUE_VIOLATION) => {
+            let constraint = err.as_db_error().and_then(|e| e.constraint());
+            return Result::Err(match constraint {
+                Some(VOTES_CHALLENGE_INDEX) => challenge::challenge_used(),
+                _ => MyError::AlreadyVoted,
+            });
+        }
+        Err(err) => return Result::Err(MyError::PGError(err)),
+    }
+
+    append_to_ballot_log(&transaction, election_id, &vote_info.uuid).await?;
+
+    transaction.commit().await.map_err(MyError::PGError)
+}
+
+/// Checks the votes recently stored from the IP address against the limit of the election and
+/// returns whether the new vote is to be flagged.
+async fn check_ip_limit(
+    transaction: &Transaction<'_>,
+    election_id: &str,
+    ip_limit: &IpLimitWeb,
+    ip_address_hash: &IpHash,
+    ip_address_hashes: &[IpHash],
+) -> Result<bool, MyError> {
+    // Serializes votes from the address until the end of the transaction, so that concurrent
+    // ones do not miss each other in the count.
+    let _stmt = include_str!("../sql/lock_ip_address.sql");
+    let stmt = transaction.prepare(_stmt).await.unwrap();
+    transaction
+        .execute(&stmt, &[&election_id, &ip_address_hash.hash])
+        .await
+        .map_err(MyError::PGError)?;
+
+    let since = Utc::now() - chrono::Duration::seconds(ip_limit.window_seconds as i64);
+    let hashes: Vec<&str> = ip_address_hashes.iter().map(|h| h.hash.as_str()).collect();
+    let _stmt = include_str!("../sql/count_votes_by_ip.sql");
+    let stmt = transaction.prepare(_stmt).await.unwrap();
+    let recent_votes: i64 = transaction
+        .query_one(&stmt, &[&election_id, &hashes, &since])
+        .await
+        .map_err(MyError::PGError)?
+        .get("count");
+
+    if recent_votes < ip_limit.max_votes {
+        return Result::Ok(false);
+    }
+    match ip_limit.action {
+        IpLimitAction::Reject => Result::Err(MyError::TooManyVotes),
+        IpLimitAction::Flag => Result::Ok(true),
+    }
+}
+
+/// Appends the stored vote to the hash chain of the election's ballot log. The leaf hash is
+/// computed from the vote as read back, the same way `/get_vote` returns it.
+async fn append_to_ballot_log(
+    transaction: &Transaction<'_>,
+    election_id: &str,
+    uuid: &String,
+) -> Result<(), MyError> {
+    let _stmt = include_str!("../sql/get_vote.sql");
+    let stmt = transaction.prepare(_stmt).await.unwrap();
+    let row = transaction
+        .query_one(&stmt, &[&uuid, &election_id])
+        .await
+        .map_err(MyError::PGError)?;
+    let revision: i32 = row.get("revision");
+    let vote = vote_from_record(VoteDB::from_row_ref(&row).unwrap());
+
+    // Serializes appends to the log of the election until the end of the transaction.
+    let _stmt = include_str!("../sql/lock_ballot_log.sql");
+    let stmt = transaction.prepare(_stmt).await.unwrap();
+    transaction
+        .execute(&stmt, &[&election_id])
+        .await
+        .map_err(MyError::PGError)?;
+
+    let previous_hash = get_ballot_log_last_hash(transaction, election_id)
+        .await?
+        .unwrap_or_else(|| "0".repeat(64));
+    let leaf_hash = sha256(&serde_json::to_string(&vote).unwrap());
+    let entry_hash = sha256(&format!("{}{}", previous_hash, leaf_hash));
+
+    let _stmt = include_str!("../sql/add_ballot_log_entry.sql");
+    let stmt = transaction.prepare(_stmt).await.unwrap();
+    transaction
+        .execute(
+            &stmt,
+            &[
+                &election_id,
+                &vote.uuid,
+                &revision,
+                &leaf_hash,
+                &previous_hash,
+                &entry_hash,
+            ],
+        )
+        .await
+        .map_err(MyError::PGError)?;
+
+    Result::Ok(())
+}
+
+/// Entry hash of the latest entry of the ballot log, `None` while the log is empty.
+async fn get_ballot_log_last_hash(
+    transaction: &Transaction<'_>,
+    election_id: &str,
+) -> Result<Option<String>, MyError> {
+    let _stmt = include_str!("../sql/get_ballot_log_last_hash.sql");
+    let stmt = transaction.prepare(_stmt).await.unwrap();
+
+    let row = transaction
+        .query_opt(&stmt, &[&election_id])
+        .await
+        .map_err(MyError::PGError)?;
+
+    Result::Ok(row.map(|row| row.get("entry_hash")))
+}
+
+pub async fn get_ballot_log_head(
+    client: &Client,
+    election_id: &str,
+) -> Result<BallotLogHeadWeb, MyError> {
+    let _stmt = include_str!("../sql/get_ballot_log_head.sql");
+    let stmt = client.prepare(_stmt).await.unwrap();
+
+    let row = client
+        .query_one(&stmt, &[&election_id])
+        .await
+        .map_err(MyError::PGError)?;
+
+    Result::Ok(BallotLogHeadWeb::from_row_ref(&row).unwrap())
+}
+
+/// Proof that the vote is included in the ballot log of the election.
+pub async fn get_inclusion_proof(
+    client: &mut Client,
+    election_id: &str,
+    uuid: &String,
+) -> Result<InclusionProofWeb, MyError> {
+    let transaction = client
+        .build_transaction()
+        .isolation_level(IsolationLevel::RepeatableRead)
+        .read_only(true)
+        .start()
+        .await
+        .map_err(MyError::PGError)?;
+
+    let _stmt = include_str!("../sql/get_ballot_log_entries.sql");
+    let stmt = transaction.prepare(_stmt).await.unwrap();
+    let entries = transaction
+        .query(&stmt, &[&election_id, &uuid])
+        .await
+        .map_err(MyError::PGError)?
+        .iter()
+        .map(|row| BallotLogEntryWeb::from_row_ref(row).unwrap())
+        .collect::<Vec<BallotLogEntryWeb>>();
+    let first_seq = entries.first().ok_or(MyError::NotFound)?.seq;
+
+    let _stmt = include_str!("../sql/get_ballot_log_leaves.sql");
+    let stmt = transaction.prepare(_stmt).await.unwrap();
+    let following_leaf_hashes = transaction
+        .query(&stmt, &[&election_id, &first_seq])
+        .await
+        .map_err(MyError::PGError)?
+        .iter()
+        .map(|row| row.get("leaf_hash"))
+        .collect::<Vec<String>>();
+
+    let head_hash = get_ballot_log_last_hash(&transaction, election_id).await?;
+
+    Result::Ok(InclusionProofWeb {
+        election_id: election_id.to_owned(),
+        uuid: uuid.to_owned(),
+        entries,
+        following_leaf_hashes,
+        head_hash: head_hash.ok_or(MyError::NotFound)?,
+    })
+}
+
+/// Replaces the order and poll answers of a vote, keeping the previous version as a revision.
+pub async fn edit_vote(
+    client: &mut Client,
+    election_id: &str,
+    edit: EditVoteWeb,
+) -> Result<(), MyError> {
+    let transaction = client.transaction().await.map_err(MyError::PGError)?;
+
+    let _stmt = include_str!("../sql/get_vote_for_edit.sql");
+    let stmt = transaction.prepare(_stmt).await.unwrap();
+    let row = transaction
+        .query_opt(&stmt, &[&edit.uuid, &election_id])
+        .await
+        .map_err(MyError::PGError)?
+        .ok_or(MyError::NotFound)?;
+    let edit_token_hash: Option<String> = row.get("edit_token_hash");
+    match edit_token_hash {
+        Some(hash) if constant_time_eq(&hash, &sha256(&edit.edit_token)) => {}
+        _ => return Result::Err(MyError::Unauthorized),
+    }
+
+    let _stmt = include_str!("../sql/add_vote_revision.sql");
+    let stmt = transaction.prepare(_stmt).await.unwrap();
+    transaction
+        .execute(&stmt, &[&edit.uuid, &election_id])
+        .await
+        .map_err(MyError::PGError)?;
+
+    let _stmt = include_str!("../sql/edit_vote.sql");
+    let stmt = transaction.prepare(_stmt).await.unwrap();
+    transaction
+        .execute(
+            &stmt,
+            &[
+                &edit.uuid,
+                &join_permutation(&edit.order),
+                &Json(&edit.polls),
+                &election_id,
+            ],
+        )
+        .await
+        .map_err(MyError::PGError)?;
+
+    append_to_ballot_log(&transaction, election_id, &edit.uuid).await?;
+
+    transaction.commit().await.map_err(MyError::PGError)
+}
+
+/// All versions of a vote, the oldest first.
+pub async fn get_vote_revisions(
+    client: &Client,
+    election_id: &str,
+    uuid: &String,
+) -> Result<Vec<VoteRevisionWeb>, MyError> {
+    let _stmt = include_str!("../sql/get_vote_revisions.sql");
+    let stmt = client.prepare(_stmt).await.unwrap();
+
+    let revisions: Vec<VoteRevisionWeb> = client
+        .query(&stmt, &[&uuid, &election_id])
+        .await
+        .map_err(MyError::PGError)?
+        .iter()
+        .map(|row| VoteRevisionDB::from_row_ref(row).unwrap())
+        .map(|record| VoteRevisionWeb {
+            revision: record.revision,
+            order: parse_permutation(&record.permutation),
+            polls: record.polls.0,
+            submitted: record.submitted,
+        })
+        .collect();
+
+    if revisions.is_empty() {
+        return Result::Err(MyError::NotFound);
     }
+    Result::Ok(revisions)
 }
 
-pub async fn get_vote(client: &Client, uuid: &String) -> Result<VoteWeb, MyError> {
+fn join_permutation(order: &[i32]) -> String {
+    let order_as_strings: Vec<String> = order.iter().map(|&v| v.to_string()).collect();
+    order_as_strings.join(",")
+}
+
+fn parse_permutation(permutation: &str) -> Vec<i32> {
+    permutation
+        .split(",")
+        .filter_map(|s| s.parse::<i32>().ok())
+        .collect()
+}
+
+pub async fn get_vote(
+    client: &Client,
+    election_id: &str,
+    uuid: &String,
+) -> Result<VoteWeb, MyError> {
     let _stmt = include_str!("../sql/get_vote.sql");
-    let stmt = client.prepare(&_stmt).await.unwrap();
+    let stmt = client.prepare(_stmt).await.unwrap();
 
     let record = client
-        .query(&stmt, &[&uuid])
+        .query(&stmt, &[&uuid, &election_id])
         .await
         .map_err(MyError::PGError)?
         .iter()
@@ -147,173 +360,77 @@ pub async fn get_vote(client: &Client, uuid: &String) -> Result<VoteWeb, MyError
         .pop()
         .ok_or(MyError::NotFound)?;
 
+    Result::Ok(vote_from_record(record))
+}
+
+fn vote_from_record(record: VoteDB) -> VoteWeb {
     let nonces_vec: Vec<String> = record.nonces.split(",").map(|s| s.to_string()).collect();
 
-    let order_vec: Vec<i32> = record
-        .permutation
-        .split(",")
-        .filter_map(|s| i32::from_str_radix(s, 10).ok())
-        .collect();
+    let order_vec: Vec<i32> = parse_permutation(&record.permutation);
 
     let uuid = record.id.to_owned();
-    let polls = vote_db_to_polls_web(record);
+    let polls = record.polls.0;
 
-    let vote_web = VoteWeb {
+    VoteWeb {
         uuid,
         nonces: nonces_vec,
+        challenge: record.challenge,
         order: order_vec,
         polls,
-    };
-
-    Result::Ok(vote_web)
+    }
 }
 
-async fn get_votes(client: &Client, date_before: &str) -> Result<Vec<PollsWeb>, MyError> {
+pub async fn get_votes(
+    client: &Client,
+    election_id: &str,
+    range: VoteRange,
+    include_flagged: bool,
+) -> Result<Vec<VotePollsDB>, MyError> {
     let _stmt = include_str!("../sql/get_votes_simple.sql");
-    let stmt = client.prepare(&_stmt).await.unwrap();
+    let stmt = client.prepare(_stmt).await.unwrap();
 
     let records = client
-        .query(&stmt, &[&date_before])
+        .query(
+            &stmt,
+            &[&election_id, &range.from, &range.to, &include_flagged],
+        )
         .await
         .map_err(MyError::PGError)?
         .iter()
-        .map(|row| VoteDB::from_row_ref(row).unwrap())
-        .collect::<Vec<VoteDB>>();
+        .map(|row| VotePollsDB::from_row_ref(row).unwrap())
+        .collect::<Vec<VotePollsDB>>();
 
-    let polls = records
-        .into_iter()
-        .map(|vote_db| vote_db_to_polls_web(vote_db))
-        .collect();
-
-    Result::Ok(polls)
+    Result::Ok(records)
 }
 
-pub async fn get_valid_votes(client: &Client) -> Result<Vec<PollsWeb>, MyError> {
-    get_votes(client, "2023-01-14 13:00:00 +00:00").await
+pub async fn get_valid_votes(
+    client: &Client,
+    election: &ElectionWeb,
+) -> Result<Vec<PollsWeb>, MyError> {
+    let records = get_votes(client, &election.id, election.voting_range(), false).await?;
+    Result::Ok(records.into_iter().map(|record| record.polls.0).collect())
 }
 
-pub async fn get_all_votes(client: &Client) -> Result<Vec<PollsWeb>, MyError> {
-    get_votes(client, "9999-01-01").await
+pub async fn get_all_votes(client: &Client, election_id: &str) -> Result<Vec<PollsWeb>, MyError> {
+    let records = get_votes(client, election_id, VoteRange::default(), true).await?;
+    Result::Ok(records.into_iter().map(|record| record.polls.0).collect())
 }
 
-fn vote_db_to_polls_web(record: VoteDB) -> PollsWeb {
-    let two_round = [
-        record.rd2_0,
-        record.rd2_1,
-        record.rd2_2,
-        record.rd2_3,
-        record.rd2_4,
-        record.rd2_5,
-        record.rd2_6,
-        record.rd2_7,
-        record.rd2_8,
-        record.rd2_9,
-    ];
-    let one_round = [
-        record.rd1_0,
-        record.rd1_1,
-        record.rd1_2,
-        record.rd1_3,
-        record.rd1_4,
-        record.rd1_5,
-        record.rd1_6,
-        record.rd1_7,
-        record.rd1_8,
-        record.rd1_9,
-    ];
-    let divide = [
-        record.div_0,
-        record.div_1,
-        record.div_2,
-        record.div_3,
-        record.div_4,
-        record.div_5,
-        record.div_6,
-        record.div_7,
-        record.div_8,
-        record.div_9,
-    ];
-    let d21 = [
-        record.d21_0,
-        record.d21_1,
-        record.d21_2,
-        record.d21_3,
-        record.d21_4,
-        record.d21_5,
-        record.d21_6,
-        record.d21_7,
-        record.d21_8,
-        record.d21_9,
-    ];
-    let doodle = [
-        record.ddl_0,
-        record.ddl_1,
-        record.ddl_2,
-        record.ddl_3,
-        record.ddl_4,
-        record.ddl_5,
-        record.ddl_6,
-        record.ddl_7,
-        record.ddl_8,
-        record.ddl_9,
-    ];
-    let order = [
-        record.ord_0,
-        record.ord_1,
-        record.ord_2,
-        record.ord_3,
-        record.ord_4,
-        record.ord_5,
-        record.ord_6,
-        record.ord_7,
-        record.ord_8,
-        record.ord_9,
-    ];
-    let star = [
-        record.str_0,
-        record.str_1,
-        record.str_2,
-        record.str_3,
-        record.str_4,
-        record.str_5,
-        record.str_6,
-        record.str_7,
-        record.str_8,
-        record.str_9,
-    ];
-    let emoji = [
-        record.emj_0,
-        record.emj_1,
-        record.emj_2,
-        record.emj_3,
-        record.emj_4,
-        record.emj_5,
-        record.emj_6,
-        record.emj_7,
-        record.emj_8,
-        record.emj_9,
-    ];
-
-    let polls = PollsWeb {
-        two_round: two_round
-            .iter()
-            .enumerate()
-            .find(|&(_i, &v)| v > 0)
-            .map(|p| p.0 as i32)
-            .unwrap_or_else(|| -1),
-        one_round: one_round
-            .iter()
-            .enumerate()
-            .find(|&(_i, &v)| v > 0)
-            .map(|p| p.0 as i32)
-            .unwrap_or_else(|| -1),
-        divide: divide.to_vec(),
-        d21: d21.to_vec(),
-        doodle: doodle.to_vec(),
-        order: order.to_vec(),
-        star: star.to_vec(),
-        emoji: emoji.to_vec(),
-    };
+pub async fn get_strength_distribution(
+    client: &Client,
+    election_id: &str,
+    range: VoteRange,
+) -> Result<Vec<StrengthCountWeb>, MyError> {
+    let _stmt = include_str!("../sql/get_strength_distribution.sql");
+    let stmt = client.prepare(_stmt).await.unwrap();
+
+    let records = client
+        .query(&stmt, &[&election_id, &range.from, &range.to])
+        .await
+        .map_err(MyError::PGError)?
+        .iter()
+        .map(|row| StrengthCountWeb::from_row_ref(row).unwrap())
+        .collect::<Vec<StrengthCountWeb>>();
 
-    polls
+    Result::Ok(records)
 }
@@ -0,0 +1,157 @@
+use crate::models::{AdaptiveDifficultyWeb, DifficultyWeb, ElectionWeb};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Recent vote submissions per election, used to raise the proof of work difficulty when the
+/// submission rate spikes.
+#[derive(Debug, Default)]
+pub struct SubmissionTracker {
+    submissions: Mutex<HashMap<String, ElectionSubmissions>>,
+}
+
+#[derive(Debug, Default)]
+struct ElectionSubmissions {
+    recent: VecDeque<Instant>,
+    /// When the difficulty rose, with the number of extra nonces required before.
+    raises: VecDeque<(Instant, usize)>,
+}
+
+impl ElectionSubmissions {
+    fn record(&mut self, adaptive: &AdaptiveDifficultyWeb, now: Instant) {
+        let before = self.extra_nonces(adaptive, now);
+        self.recent.push_back(now);
+        if self.extra_nonces(adaptive, now) > before {
+            self.raises.push_back((now, before));
+        }
+    }
+
+    fn extra_nonces(&mut self, adaptive: &AdaptiveDifficultyWeb, now: Instant) -> usize {
+        let window = Duration::from_secs(adaptive.window_seconds);
+        while let Some(&oldest) = self.recent.front() {
+            if now.duration_since(oldest) < window {
+                break;
+            }
+            self.recent.pop_front();
+        }
+        let steps = self.recent.len() / adaptive.submissions_per_window;
+        (steps * adaptive.extra_nonces_step).min(adaptive.max_extra_nonces)
+    }
+
+    /// Lowest number of extra nonces required within the grace period.
+    fn accepted_extra_nonces(&mut self, adaptive: &AdaptiveDifficultyWeb, now: Instant) -> usize {
+        let grace = Duration::from_secs(adaptive.grace_seconds);
+        while let Some(&(raised, _)) = self.raises.front() {
+            if now.duration_since(raised) < grace {
+                break;
+            }
+            self.raises.pop_front();
+        }
+        let current = self.extra_nonces(adaptive, now);
+        self.raises
+            .iter()
+            .map(|&(_, before)| before)
+            .fold(current, usize::min)
+    }
+}
+
+impl SubmissionTracker {
+    /// Counts an accepted vote.
+    pub fn record(&self, election: &ElectionWeb) {
+        if let Some(adaptive) = &election.proof_of_work.adaptive {
+            let mut submissions = self.submissions.lock().unwrap();
+            let state = submissions.entry(election.id.clone()).or_default();
+            state.record(adaptive, Instant::now());
+        }
+    }
+
+    /// Difficulty a vote submitted now has to satisfy, as reported to clients.
+    pub fn difficulty(&self, election: &ElectionWeb) -> DifficultyWeb {
+        self.difficulty_with(election, ElectionSubmissions::extra_nonces)
+    }
+
+    /// Difficulty a vote is checked against. A vote computed against the difficulty reported
+    /// before it rose is still accepted within the grace period of the election.
+    pub fn accepted_difficulty(&self, election: &ElectionWeb) -> DifficultyWeb {
+        self.difficulty_with(election, ElectionSubmissions::accepted_extra_nonces)
+    }
+
+    fn difficulty_with(
+        &self,
+        election: &ElectionWeb,
+        extra_nonces: fn(&mut ElectionSubmissions, &AdaptiveDifficultyWeb, Instant) -> usize,
+    ) -> DifficultyWeb {
+        let pow = &election.proof_of_work;
+        let extra_nonces = match &pow.adaptive {
+            Some(adaptive) => {
+                let mut submissions = self.submissions.lock().unwrap();
+                match submissions.get_mut(&election.id) {
+                    Some(state) => extra_nonces(state, adaptive, Instant::now()),
+                    None => 0,
+                }
+            }
+            None => 0,
+        };
+        DifficultyWeb {
+            election_id: election.id.clone(),
+            prefix: pow.prefix.clone(),
+            salt: pow.salt.clone(),
+            min_nonces: pow.min_nonces + extra_nonces,
+            raised: extra_nonces > 0,
+            challenge_required: pow.challenge_ttl_seconds.is_some(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn adaptive() -> AdaptiveDifficultyWeb {
+        AdaptiveDifficultyWeb {
+            window_seconds: 60,
+            submissions_per_window: 2,
+            extra_nonces_step: 1,
+            max_extra_nonces: 2,
+            grace_seconds: 10,
+        }
+    }
+
+    fn after(start: Instant, seconds: u64) -> Instant {
+        start + Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn difficulty_rises_with_submissions_in_the_window() {
+        let adaptive = adaptive();
+        let start = Instant::now();
+        let mut state = ElectionSubmissions::default();
+
+        state.record(&adaptive, start);
+        assert_eq!(state.extra_nonces(&adaptive, start), 0);
+        state.record(&adaptive, after(start, 1));
+        assert_eq!(state.extra_nonces(&adaptive, after(start, 1)), 1);
+        for second in 2..10 {
+            state.record(&adaptive, after(start, second));
+        }
+        assert_eq!(state.extra_nonces(&adaptive, after(start, 10)), 2);
+
+        // Submissions older than the window no longer count.
+        assert_eq!(state.extra_nonces(&adaptive, after(start, 69)), 0);
+    }
+
+    #[test]
+    fn lower_difficulty_is_accepted_within_the_grace_period() {
+        let adaptive = adaptive();
+        let start = Instant::now();
+        let mut state = ElectionSubmissions::default();
+
+        state.record(&adaptive, start);
+        state.record(&adaptive, after(start, 1));
+
+        assert_eq!(state.extra_nonces(&adaptive, after(start, 5)), 1);
+        assert_eq!(state.accepted_extra_nonces(&adaptive, after(start, 5)), 0);
+        assert_eq!(state.accepted_extra_nonces(&adaptive, after(start, 10)), 0);
+        assert_eq!(state.accepted_extra_nonces(&adaptive, after(start, 11)), 1);
+    }
+}
@@ -1,31 +1,111 @@
+use crate::models::ErrorWeb;
+use actix_web::error::BlockingError;
+use actix_web::http::StatusCode;
 use actix_web::{HttpResponse, ResponseError};
 use deadpool_postgres::PoolError;
 use derive_more::{Display, From};
 use tokio_pg_mapper::Error as PGMError;
 use tokio_postgres::error::Error as PGError;
 
+/// Rejected request input.
+#[derive(Display, Debug)]
+#[display(fmt = "{}", message)]
+pub struct InvalidInput {
+    /// Machine-readable reason, e.g. `invalid_poll`.
+    pub code: &'static str,
+    /// Path of the offending field of the request, e.g. `polls.d21`.
+    pub field: Option<String>,
+    pub message: String,
+}
+
 #[derive(Display, From, Debug)]
 pub enum MyError {
     NotFound,
     PGError(PGError),
     PGMError(PGMError),
     PoolError(PoolError),
-    ValidationError(String),
+    BlockingError(BlockingError),
+    ValidationError(InvalidInput),
+    VotingClosed,
+    Unauthorized,
+    TooManyVotes,
+    AlreadyVoted,
 }
 impl std::error::Error for MyError {}
 
-impl ResponseError for MyError {
-    fn error_response(&self) -> HttpResponse {
-        match *self {
-            MyError::NotFound => HttpResponse::NotFound().finish(),
-            MyError::PoolError(ref err) => {
-                HttpResponse::InternalServerError().body(err.to_string())
+impl MyError {
+    pub fn invalid(code: &'static str, field: &str, message: &str) -> MyError {
+        MyError::ValidationError(InvalidInput {
+            code,
+            field: Some(field.to_owned()),
+            message: message.to_owned(),
+        })
+    }
+
+    /// Machine-readable error code and the message for the client.
+    fn code_and_message(&self) -> (&'static str, String) {
+        match self {
+            MyError::NotFound => ("not_found", "Not found.".to_owned()),
+            MyError::PoolError(_) => (
+                "service_unavailable",
+                "The database is not available.".to_owned(),
+            ),
+            MyError::PGError(err) if err.is_closed() => (
+                "service_unavailable",
+                "The database is not available.".to_owned(),
+            ),
+            MyError::PGError(_) | MyError::PGMError(_) | MyError::BlockingError(_) => {
+                ("internal_error", "Internal server error.".to_owned())
             }
-            MyError::PGError(ref err) => HttpResponse::InternalServerError().body(err.to_string()),
-            MyError::ValidationError(ref description) => {
-                HttpResponse::InternalServerError().body(description.to_string())
+            MyError::ValidationError(invalid) => (invalid.code, invalid.message.to_owned()),
+            MyError::VotingClosed => ("voting_closed", "Voting is not open.".to_owned()),
+            MyError::Unauthorized => ("unauthorized", "Unauthorized.".to_owned()),
+            MyError::TooManyVotes => (
+                "too_many_votes",
+                "Too many votes from this address.".to_owned(),
+            ),
+            MyError::AlreadyVoted => ("vote_exists", "The vote already exists.".to_owned()),
+        }
+    }
+}
+
+impl ResponseError for MyError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            MyError::NotFound => StatusCode::NOT_FOUND,
+            MyError::PoolError(_) => StatusCode::SERVICE_UNAVAILABLE,
+            MyError::PGError(err) if err.is_closed() => StatusCode::SERVICE_UNAVAILABLE,
+            MyError::PGError(_) | MyError::PGMError(_) | MyError::BlockingError(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
             }
-            _ => HttpResponse::InternalServerError().finish(),
+            MyError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            MyError::VotingClosed => StatusCode::FORBIDDEN,
+            MyError::Unauthorized => StatusCode::UNAUTHORIZED,
+            MyError::TooManyVotes => StatusCode::TOO_MANY_REQUESTS,
+            MyError::AlreadyVoted => StatusCode::CONFLICT,
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        let (code, message) = self.code_and_message();
+        let field = match self {
+            MyError::ValidationError(invalid) => invalid.field.to_owned(),
+            _ => None,
+        };
+        HttpResponse::build(self.status_code()).json(ErrorWeb {
+            code: code.to_owned(),
+            message,
+            field,
+        })
+    }
+}
+
+/// Rejects request bodies, paths and queries that cannot be parsed with the JSON error body.
+pub fn invalid_request(code: &'static str, message: String) -> actix_web::Error {
+    MyError::ValidationError(InvalidInput {
+        code,
+        field: None,
+        message,
+    })
+    .into()
 }
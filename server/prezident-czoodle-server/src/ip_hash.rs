@@ -0,0 +1,123 @@
+use crate::crypto_utils::{hmac_sha256, random_hex, sha256};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::fs;
+use std::io::Write;
+
+/// Key id of votes hashed as `sha256(ip + hash_salt)` before keyed hashing was introduced.
+pub const LEGACY_KEY_ID: &str = "legacy";
+
+/// Secret key of the IP address HMAC, stored in the IP hash keys file.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct IpHashKey {
+    pub id: String,
+    pub secret: String,
+    pub created: DateTime<Utc>,
+}
+
+/// IP address hash together with the id of the key it was computed with.
+#[derive(Debug, Clone)]
+pub struct IpHash {
+    pub key_id: String,
+    pub hash: String,
+}
+
+/// Hashes IP addresses with HMAC-SHA256. New votes use the newest key, all active keys are used
+/// to recognize votes from the same address across a key rotation.
+#[derive(Debug)]
+pub struct IpHasher {
+    /// Active keys, the newest first.
+    keys: Vec<IpHashKey>,
+    legacy_salt: Option<String>,
+}
+
+impl IpHasher {
+    /// Loads the keys file. Without any key, the legacy salted SHA-256 is used for new votes.
+    pub fn load(keys_file: &str, legacy_salt: Option<String>) -> Result<Self, String> {
+        let mut keys = read_keys(keys_file)?;
+        keys.sort_by_key(|key| Reverse(key.created));
+        if keys.is_empty() && legacy_salt.is_none() {
+            return Result::Err(format!(
+                "No IP hash key in '{}' and no hash salt, run the rotate-ip-hash-keys command.",
+                keys_file
+            ));
+        }
+        Result::Ok(IpHasher { keys, legacy_salt })
+    }
+
+    pub fn hash(&self, ip_address: &str) -> IpHash {
+        match self.keys.first() {
+            Some(key) => hash_with_key(key, ip_address),
+            None => self.legacy_hash(ip_address).unwrap(),
+        }
+    }
+
+    /// Hashes of the IP address with every active key, including the legacy salt.
+    pub fn all_hashes(&self, ip_address: &str) -> Vec<IpHash> {
+        self.keys
+            .iter()
+            .map(|key| hash_with_key(key, ip_address))
+            .chain(self.legacy_hash(ip_address))
+            .collect()
+    }
+
+    fn legacy_hash(&self, ip_address: &str) -> Option<IpHash> {
+        self.legacy_salt.as_ref().map(|salt| IpHash {
+            key_id: LEGACY_KEY_ID.to_owned(),
+            hash: sha256(&format!("{}{}", ip_address, salt)),
+        })
+    }
+}
+
+fn hash_with_key(key: &IpHashKey, ip_address: &str) -> IpHash {
+    IpHash {
+        key_id: key.id.clone(),
+        hash: hmac_sha256(key.secret.as_bytes(), ip_address),
+    }
+}
+
+fn read_keys(keys_file: &str) -> Result<Vec<IpHashKey>, String> {
+    match fs::read_to_string(keys_file) {
+        Ok(content) => serde_json::from_str(&content)
+            .map_err(|err| format!("Invalid IP hash keys file '{}': {}", keys_file, err)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Result::Ok(Vec::new()),
+        Err(err) => Result::Err(format!("Cannot read '{}': {}", keys_file, err)),
+    }
+}
+
+/// Adds a new key to the keys file and keeps only the `keep` newest keys. Keep the previous key
+/// at least as long as the longest IP limit window, so that duplicate detection keeps working.
+/// The server has to be restarted to use the new key.
+pub fn rotate_keys(keys_file: &str, keep: usize) -> Result<IpHashKey, String> {
+    let mut keys = read_keys(keys_file)?;
+    let created = Utc::now();
+    let key = IpHashKey {
+        id: created.format("%Y%m%d%H%M%S").to_string(),
+        secret: random_hex(32),
+        created,
+    };
+    if keys.iter().any(|k| k.id == key.id) {
+        return Result::Err(format!("Key '{}' already exists.", key.id));
+    }
+    keys.insert(0, key.clone());
+    keys.truncate(keep.max(1));
+
+    let content = serde_json::to_string_pretty(&keys).map_err(|err| err.to_string())?;
+    write_private(keys_file, &content)
+        .map_err(|err| format!("Cannot write '{}': {}", keys_file, err))?;
+    Result::Ok(key)
+}
+
+/// Writes a file readable by its owner only, also when it already exists.
+fn write_private(path: &str, content: &str) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    file.write_all(content.as_bytes())
+}
@@ -1,108 +1,451 @@
+mod challenge;
+mod client_ip;
 mod config;
 mod crypto_utils;
 mod db;
+mod difficulty;
 mod errors;
+mod ip_hash;
 mod models;
+mod receipt;
+mod tally;
 mod validations;
 
-use crate::errors::MyError;
-use crate::models::{PollsWeb, VoteWeb};
+use crate::errors::{InvalidInput, MyError};
+use crate::ip_hash::IpHasher;
+use crate::models::{
+    AddVoteResultWeb, BallotLogHeadWeb, ChallengeWeb, DifficultyWeb, EditVoteWeb, ElectionPath,
+    ElectionWeb, InclusionProofWeb, PollsWeb, ReceiptStatus, ReceiptVerificationWeb, ReceiptWeb,
+    ResultsQuery, ResultsWeb, StatsQuery, StrengthStatsWeb, VotePath, VotePollsDB, VoteRevisionWeb,
+    VoteWeb,
+};
+use crate::receipt::ReceiptSigner;
 use ::config::Config;
 use actix_web::{web, App, Error, HttpRequest, HttpResponse, HttpServer};
+use chrono::Utc;
 use deadpool_postgres::{Client, Pool};
 use dotenv::dotenv;
 use std::sync::Arc;
 use tokio_postgres::NoTls;
 
+use crate::challenge::ChallengeStore;
+use crate::client_ip::ClientIpResolver;
 use crate::config::ExampleConfig;
+use crate::difficulty::SubmissionTracker;
+
+/// Lifetime of challenges in elections that do not require them.
+const DEFAULT_CHALLENGE_TTL_SECONDS: u64 = 600;
 
 #[derive(Debug, Clone)]
 pub struct HandlerConfig {
-    pub ip_hash_salt: Arc<String>,
+    pub ip_hasher: Arc<IpHasher>,
+    pub client_ip: Arc<ClientIpResolver>,
+    pub elections: Arc<Vec<ElectionWeb>>,
+    pub submissions: Arc<SubmissionTracker>,
+    pub challenges: Arc<ChallengeStore>,
+    pub admin_token: Arc<Option<String>>,
+    pub receipts: Arc<ReceiptSigner>,
+}
+
+impl HandlerConfig {
+    /// Election with the given id, or the default (first configured) one when no id is given.
+    pub fn election(&self, election_id: &Option<String>) -> Result<&ElectionWeb, MyError> {
+        match election_id {
+            Some(id) => self.elections.iter().find(|e| &e.id == id),
+            None => self.elections.first(),
+        }
+        .ok_or(MyError::NotFound)
+    }
+
+    /// Checks the `Authorization: Bearer <admin token>` header of admin requests.
+    pub fn authorize_admin(&self, req: &HttpRequest) -> Result<(), MyError> {
+        let admin_token = self.admin_token.as_deref().ok_or(MyError::NotFound)?;
+        let token = req
+            .headers()
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .ok_or(MyError::Unauthorized)?;
+        if !crypto_utils::constant_time_eq(token, admin_token) {
+            return Result::Err(MyError::Unauthorized);
+        }
+        Result::Ok(())
+    }
 }
 
 pub async fn add_vote(
     req: HttpRequest,
+    path: web::Path<ElectionPath>,
     vote: web::Json<VoteWeb>,
     db_pool: web::Data<Pool>,
     handler_config: web::Data<HandlerConfig>,
 ) -> Result<HttpResponse, Error> {
-    let vote_info: VoteWeb = vote.into_inner();
+    let election = handler_config.election(&path.election_id)?;
+    if !election.is_open(Utc::now()) {
+        return Result::Err(MyError::VotingClosed.into());
+    }
+    let mut vote_info: VoteWeb = vote.into_inner();
+
+    let difficulty = handler_config.submissions.accepted_difficulty(election);
+
+    validations::validate_vote(
+        &vote_info,
+        election,
+        &difficulty,
+        &handler_config.challenges,
+    )?;
+    validations::normalize_polls(&mut vote_info.polls);
+
+    let ip_address = handler_config
+        .client_ip
+        .resolve(&req)
+        .ok_or_else(|| {
+            MyError::ValidationError(InvalidInput {
+                code: "unknown_client_address",
+                field: None,
+                message: "Unknown client address.".to_owned(),
+            })
+        })?
+        .to_string();
+
+    let ip_address_hash = handler_config.ip_hasher.hash(&ip_address);
+    let ip_address_hashes = handler_config.ip_hasher.all_hashes(&ip_address);
+
+    let mut client: Client = db_pool.get().await.map_err(MyError::PoolError)?;
+
+    let challenge = vote_info.challenge.clone();
+    let edit_token = crypto_utils::random_hex(32);
+    let result = AddVoteResultWeb {
+        uuid: vote_info.uuid.clone(),
+        edit_token: edit_token.clone(),
+        receipt: handler_config.receipts.issue(
+            &election.id,
+            &vote_info.uuid,
+            &vote_info.order,
+            &vote_info.polls,
+        ),
+    };
+
+    db::add_vote(
+        &mut client,
+        election,
+        vote_info,
+        &ip_address_hash,
+        &ip_address_hashes,
+        &crypto_utils::sha256(&edit_token),
+    )
+    .await?;
+    handler_config.submissions.record(election);
+    if let Some(challenge) = &challenge {
+        handler_config.challenges.redeem(challenge);
+    }
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+pub async fn edit_vote(
+    path: web::Path<ElectionPath>,
+    edit: web::Json<EditVoteWeb>,
+    db_pool: web::Data<Pool>,
+    handler_config: web::Data<HandlerConfig>,
+) -> Result<HttpResponse, Error> {
+    let election = handler_config.election(&path.election_id)?;
+    if !election.is_open(Utc::now()) {
+        return Result::Err(MyError::VotingClosed.into());
+    }
+    let mut edit: EditVoteWeb = edit.into_inner();
+
+    validations::validate_ballot(&edit.order, &edit.polls, election)?;
+    validations::normalize_polls(&mut edit.polls);
 
-    validations::validate_vote(&vote_info)?;
+    let mut client: Client = db_pool.get().await.map_err(MyError::PoolError)?;
 
-    let ip_address = req
-        .headers()
-        .get("x-real-ip")
-        .map(|v| v.to_str().unwrap_or_else(|_| ""))
-        .unwrap_or_else(|| "");
+    let receipt: ReceiptWeb =
+        handler_config
+            .receipts
+            .issue(&election.id, &edit.uuid, &edit.order, &edit.polls);
+    db::edit_vote(&mut client, &election.id, edit).await?;
 
-    let ip_address_hash =
-        crypto_utils::sha256(&format!("{}{}", &ip_address, &handler_config.ip_hash_salt));
+    Ok(HttpResponse::Ok().json(receipt))
+}
 
+pub async fn verify_receipt(
+    receipt: web::Json<ReceiptWeb>,
+    db_pool: web::Data<Pool>,
+    handler_config: web::Data<HandlerConfig>,
+) -> Result<HttpResponse, Error> {
+    let receipt: ReceiptWeb = receipt.into_inner();
+    if !handler_config.receipts.verify(&receipt) {
+        return Ok(HttpResponse::Ok().json(ReceiptVerificationWeb {
+            status: ReceiptStatus::InvalidSignature,
+            revision: None,
+        }));
+    }
     let client: Client = db_pool.get().await.map_err(MyError::PoolError)?;
 
-    db::add_vote(&client, vote_info, &ip_address_hash).await?;
+    let revisions = match db::get_vote_revisions(&client, &receipt.election_id, &receipt.uuid).await
+    {
+        Ok(revisions) => revisions,
+        Err(MyError::NotFound) => Vec::new(),
+        Err(err) => return Result::Err(err.into()),
+    };
+    let result: ReceiptVerificationWeb = receipt::check_revisions(&receipt, &revisions);
 
-    Ok(HttpResponse::Ok().finish())
+    Ok(HttpResponse::Ok().json(result))
 }
 
 pub async fn get_vote(
-    path: web::Path<String>,
+    path: web::Path<VotePath>,
+    db_pool: web::Data<Pool>,
+    handler_config: web::Data<HandlerConfig>,
+) -> Result<HttpResponse, Error> {
+    let path: VotePath = path.into_inner();
+    let election = handler_config.election(&path.election_id)?;
+    let client: Client = db_pool.get().await.map_err(MyError::PoolError)?;
+
+    let result: VoteWeb = db::get_vote(&client, &election.id, &path.uuid).await?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+pub async fn get_valid_votes(
+    path: web::Path<ElectionPath>,
     db_pool: web::Data<Pool>,
+    handler_config: web::Data<HandlerConfig>,
 ) -> Result<HttpResponse, Error> {
-    let uuid: String = path.into_inner();
+    let election = handler_config.election(&path.election_id)?;
     let client: Client = db_pool.get().await.map_err(MyError::PoolError)?;
 
-    let result: VoteWeb = db::get_vote(&client, &uuid).await?;
+    let result: Vec<PollsWeb> = db::get_valid_votes(&client, election).await?;
 
     Ok(HttpResponse::Ok().json(result))
 }
 
-pub async fn get_valid_votes(db_pool: web::Data<Pool>) -> Result<HttpResponse, Error> {
+pub async fn get_all_votes(
+    path: web::Path<ElectionPath>,
+    db_pool: web::Data<Pool>,
+    handler_config: web::Data<HandlerConfig>,
+) -> Result<HttpResponse, Error> {
+    let election = handler_config.election(&path.election_id)?;
     let client: Client = db_pool.get().await.map_err(MyError::PoolError)?;
 
-    let result: Vec<PollsWeb> = db::get_valid_votes(&client).await?;
+    let result: Vec<PollsWeb> = db::get_all_votes(&client, &election.id).await?;
 
     Ok(HttpResponse::Ok().json(result))
 }
 
-pub async fn get_all_votes(db_pool: web::Data<Pool>) -> Result<HttpResponse, Error> {
+pub async fn get_results(
+    path: web::Path<ElectionPath>,
+    query: web::Query<ResultsQuery>,
+    db_pool: web::Data<Pool>,
+    handler_config: web::Data<HandlerConfig>,
+) -> Result<HttpResponse, Error> {
+    let election = handler_config.election(&path.election_id)?;
+    let range = query.vote_range(election)?;
     let client: Client = db_pool.get().await.map_err(MyError::PoolError)?;
 
-    let result: Vec<PollsWeb> = db::get_all_votes(&client).await?;
+    let votes: Vec<VotePollsDB> = db::get_votes(
+        &client,
+        &election.id,
+        range,
+        query.include_flagged.unwrap_or(false),
+    )
+    .await?;
+    // Tallying many ballots takes a while, so it does not run on the worker thread.
+    let election = election.clone();
+    let query: ResultsQuery = query.into_inner();
+    let result: ResultsWeb = web::block(move || tally::tally(&votes, &election, &query))
+        .await
+        .map_err(MyError::BlockingError)??;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+pub async fn get_difficulty(
+    path: web::Path<ElectionPath>,
+    handler_config: web::Data<HandlerConfig>,
+) -> Result<HttpResponse, Error> {
+    let election = handler_config.election(&path.election_id)?;
+    let result: DifficultyWeb = handler_config.submissions.difficulty(election);
 
     Ok(HttpResponse::Ok().json(result))
 }
 
+pub async fn issue_challenge(
+    path: web::Path<ElectionPath>,
+    handler_config: web::Data<HandlerConfig>,
+) -> Result<HttpResponse, Error> {
+    let election = handler_config.election(&path.election_id)?;
+    let ttl_seconds = election
+        .proof_of_work
+        .challenge_ttl_seconds
+        .unwrap_or(DEFAULT_CHALLENGE_TTL_SECONDS);
+    let result: ChallengeWeb = handler_config.challenges.issue(election, ttl_seconds);
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+pub async fn get_strength_stats(
+    req: HttpRequest,
+    path: web::Path<ElectionPath>,
+    query: web::Query<StatsQuery>,
+    db_pool: web::Data<Pool>,
+    handler_config: web::Data<HandlerConfig>,
+) -> Result<HttpResponse, Error> {
+    handler_config.authorize_admin(&req)?;
+    let election = handler_config.election(&path.election_id)?;
+    let range = query.vote_range(election)?;
+    let client: Client = db_pool.get().await.map_err(MyError::PoolError)?;
+
+    let distribution = db::get_strength_distribution(&client, &election.id, range).await?;
+    let result: StrengthStatsWeb = tally::strength_stats(&election.id, distribution);
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+pub async fn get_vote_revisions(
+    req: HttpRequest,
+    path: web::Path<VotePath>,
+    db_pool: web::Data<Pool>,
+    handler_config: web::Data<HandlerConfig>,
+) -> Result<HttpResponse, Error> {
+    handler_config.authorize_admin(&req)?;
+    let path: VotePath = path.into_inner();
+    let election = handler_config.election(&path.election_id)?;
+    let client: Client = db_pool.get().await.map_err(MyError::PoolError)?;
+
+    let result: Vec<VoteRevisionWeb> =
+        db::get_vote_revisions(&client, &election.id, &path.uuid).await?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+pub async fn get_ballot_log_head(
+    path: web::Path<ElectionPath>,
+    db_pool: web::Data<Pool>,
+    handler_config: web::Data<HandlerConfig>,
+) -> Result<HttpResponse, Error> {
+    let election = handler_config.election(&path.election_id)?;
+    let client: Client = db_pool.get().await.map_err(MyError::PoolError)?;
+
+    let result: BallotLogHeadWeb = db::get_ballot_log_head(&client, &election.id).await?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+pub async fn get_inclusion_proof(
+    path: web::Path<VotePath>,
+    db_pool: web::Data<Pool>,
+    handler_config: web::Data<HandlerConfig>,
+) -> Result<HttpResponse, Error> {
+    let path: VotePath = path.into_inner();
+    let election = handler_config.election(&path.election_id)?;
+    let mut client: Client = db_pool.get().await.map_err(MyError::PoolError)?;
+
+    let result: InclusionProofWeb =
+        db::get_inclusion_proof(&mut client, &election.id, &path.uuid).await?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+pub async fn get_elections(handler_config: web::Data<HandlerConfig>) -> HttpResponse {
+    HttpResponse::Ok().json(handler_config.elections.as_ref())
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenv().ok();
 
     let config_ = Config::builder()
+        .add_source(::config::File::with_name("config").required(false))
         .add_source(::config::Environment::default())
         .build()
         .unwrap();
 
     let config: ExampleConfig = config_.try_deserialize().unwrap();
+    config.validate().unwrap();
+
+    if std::env::args().nth(1).as_deref() == Some("rotate-ip-hash-keys") {
+        let keep = std::env::args()
+            .nth(2)
+            .map(|keep| keep.parse().expect("Number of keys to keep expected."))
+            .unwrap_or(2);
+        let key = ip_hash::rotate_keys(&config.ip_hash_keys_file, keep).unwrap();
+        println!(
+            "Added IP hash key '{}' to {}, restart the server to use it.",
+            key.id, config.ip_hash_keys_file
+        );
+        return Ok(());
+    }
+
+    let ip_hasher = IpHasher::load(&config.ip_hash_keys_file, config.hash_salt).unwrap();
+    let client_ip =
+        ClientIpResolver::new(&config.trusted_proxies, config.client_ip_header).unwrap();
 
     let pool = config.pg.create_pool(None, NoTls).unwrap();
 
     let handler_config = HandlerConfig {
-        ip_hash_salt: Arc::new(config.hash_salt),
+        ip_hasher: Arc::new(ip_hasher),
+        client_ip: Arc::new(client_ip),
+        elections: Arc::new(config.elections),
+        submissions: Arc::new(SubmissionTracker::default()),
+        challenges: Arc::new(ChallengeStore::new(config.challenge_secret.as_deref())),
+        admin_token: Arc::new(config.admin_token),
+        receipts: Arc::new(ReceiptSigner::new(config.receipt_secret.as_deref())),
     };
 
-    let server = HttpServer::new(move || {
-        App::new()
-            .app_data(web::Data::new(pool.clone()))
-            .app_data(web::Data::new(handler_config.clone()))
-            .route("/add_vote", web::post().to(add_vote))
-            .route("/get_vote/{uuid}", web::get().to(get_vote))
-            .route("/get_valid_votes", web::get().to(get_valid_votes))
-            .route("/get_all_votes", web::get().to(get_all_votes))
-    })
-    .bind(config.server_addr.clone())?
-    .run();
+    let server =
+        HttpServer::new(move || {
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(handler_config.clone()))
+                .app_data(web::JsonConfig::default().error_handler(|err, _| {
+                    errors::invalid_request("invalid_json", err.to_string())
+                }))
+                .app_data(web::QueryConfig::default().error_handler(|err, _| {
+                    errors::invalid_request("invalid_query", err.to_string())
+                }))
+                .app_data(web::PathConfig::default().error_handler(|err, _| {
+                    errors::invalid_request("invalid_path", err.to_string())
+                }))
+                .route("/add_vote", web::post().to(add_vote))
+                .route("/edit_vote", web::post().to(edit_vote))
+                .route("/get_vote/{uuid}", web::get().to(get_vote))
+                .route("/get_valid_votes", web::get().to(get_valid_votes))
+                .route("/get_all_votes", web::get().to(get_all_votes))
+                .route("/results", web::get().to(get_results))
+                .route("/difficulty", web::get().to(get_difficulty))
+                .route("/challenge", web::post().to(issue_challenge))
+                .route("/admin/stats", web::get().to(get_strength_stats))
+                .route(
+                    "/admin/get_vote_revisions/{uuid}",
+                    web::get().to(get_vote_revisions),
+                )
+                .route("/verify_receipt", web::post().to(verify_receipt))
+                .route("/ballot_log", web::get().to(get_ballot_log_head))
+                .route("/ballot_log/{uuid}", web::get().to(get_inclusion_proof))
+                .route("/elections", web::get().to(get_elections))
+                .service(
+                    web::scope("/elections/{election_id}")
+                        .route("/add_vote", web::post().to(add_vote))
+                        .route("/edit_vote", web::post().to(edit_vote))
+                        .route("/get_vote/{uuid}", web::get().to(get_vote))
+                        .route("/get_valid_votes", web::get().to(get_valid_votes))
+                        .route("/get_all_votes", web::get().to(get_all_votes))
+                        .route("/results", web::get().to(get_results))
+                        .route("/difficulty", web::get().to(get_difficulty))
+                        .route("/challenge", web::post().to(issue_challenge))
+                        .route("/admin/stats", web::get().to(get_strength_stats))
+                        .route("/ballot_log", web::get().to(get_ballot_log_head))
+                        .route("/ballot_log/{uuid}", web::get().to(get_inclusion_proof))
+                        .route(
+                            "/admin/get_vote_revisions/{uuid}",
+                            web::get().to(get_vote_revisions),
+                        ),
+                )
+        })
+        .bind(config.server_addr.clone())?
+        .run();
     println!("Server running at http://{}/", config.server_addr);
 
     server.await
@@ -1,129 +1,645 @@
+use crate::errors::MyError;
+use chrono::{DateTime, Utc};
+use derive_more::Display;
 use serde::{Deserialize, Serialize};
 use tokio_pg_mapper_derive::PostgresMapper;
+use tokio_postgres::types::Json;
 
 #[derive(Deserialize, Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct PollsWeb {
-    pub two_round: i32,
-    pub one_round: i32,
-    pub divide: Vec<i32>,
-    pub d21: Vec<i32>,
-    pub doodle: Vec<i32>,
-    pub order: Vec<i32>,
-    pub star: Vec<i32>,
-    pub emoji: Vec<String>,
+    pub two_round: Option<i32>,
+    pub one_round: Option<i32>,
+    pub divide: Option<Vec<i32>>,
+    pub d21: Option<Vec<i32>>,
+    pub doodle: Option<Vec<i32>>,
+    pub order: Option<Vec<i32>>,
+    pub star: Option<Vec<i32>>,
+    pub emoji: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Display, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum PollType {
+    #[display(fmt = "two-round")]
+    TwoRound,
+    #[display(fmt = "one-round")]
+    OneRound,
+    #[display(fmt = "divide")]
+    Divide,
+    #[display(fmt = "D21")]
+    D21,
+    #[display(fmt = "Doodle")]
+    Doodle,
+    #[display(fmt = "order")]
+    Order,
+    #[display(fmt = "star")]
+    Star,
+    #[display(fmt = "emoji")]
+    Emoji,
+}
+
+pub fn all_poll_types() -> Vec<PollType> {
+    vec![
+        PollType::TwoRound,
+        PollType::OneRound,
+        PollType::Divide,
+        PollType::D21,
+        PollType::Doodle,
+        PollType::Order,
+        PollType::Star,
+        PollType::Emoji,
+    ]
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CandidateWeb {
+    /// Index of the candidate in all per-candidate poll arrays.
+    pub id: i32,
+    pub name: String,
+    /// Short code used in reports, e.g. initials.
+    pub code: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ElectionWeb {
+    pub id: String,
+    pub title: String,
+    pub candidates: Vec<CandidateWeb>,
+    /// Polls voters answer in this election, all of them by default.
+    #[serde(default = "all_poll_types")]
+    pub polls: Vec<PollType>,
+    /// Start of the voting window, unbounded if not set.
+    #[serde(default)]
+    pub opens: Option<DateTime<Utc>>,
+    /// End of the voting window (exclusive), unbounded if not set.
+    #[serde(default)]
+    pub closes: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub proof_of_work: ProofOfWorkWeb,
+    /// Limit of votes from one IP address (hash), not published to clients.
+    #[serde(default, skip_serializing)]
+    pub ip_limit: Option<IpLimitWeb>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct IpLimitWeb {
+    /// Votes accepted from one IP address hash within the window.
+    pub max_votes: i64,
+    pub window_seconds: u64,
+    #[serde(default)]
+    pub action: IpLimitAction,
+}
+
+/// What happens with votes over the IP limit.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum IpLimitAction {
+    Reject,
+    /// Store the vote, but leave it out of valid results.
+    #[default]
+    Flag,
+}
+
+/// Proof of work required for a vote: a chain of SHA-256 hashes starting from `uuid + salt`,
+/// each hash of `previous + nonce` has to start with `prefix`.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ProofOfWorkWeb {
+    #[serde(default = "default_pow_prefix")]
+    pub prefix: String,
+    #[serde(default = "default_pow_min_nonces")]
+    pub min_nonces: usize,
+    #[serde(default = "default_pow_salt")]
+    pub salt: String,
+    /// Raises the minimum chain length when votes come in faster than usual.
+    #[serde(default)]
+    pub adaptive: Option<AdaptiveDifficultyWeb>,
+    /// When set, votes have to fold a challenge from `/challenge` valid for the given number
+    /// of seconds into the chain, which prevents precomputing votes offline.
+    #[serde(default)]
+    pub challenge_ttl_seconds: Option<u64>,
+}
+
+fn default_pow_prefix() -> String {
+    "777".to_owned()
+}
+
+fn default_pow_min_nonces() -> usize {
+    5
+}
+
+fn default_pow_salt() -> String {
+    "czoodle".to_owned()
+}
+
+impl Default for ProofOfWorkWeb {
+    fn default() -> Self {
+        ProofOfWorkWeb {
+            prefix: default_pow_prefix(),
+            min_nonces: default_pow_min_nonces(),
+            salt: default_pow_salt(),
+            adaptive: None,
+            challenge_ttl_seconds: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AdaptiveDifficultyWeb {
+    /// Length of the sliding window submissions are counted in.
+    pub window_seconds: u64,
+    /// Submissions per window tolerated without raising the difficulty.
+    pub submissions_per_window: usize,
+    /// Nonces added to the minimum chain length for every further `submissions_per_window`.
+    #[serde(default = "default_adaptive_step")]
+    pub extra_nonces_step: usize,
+    /// Upper bound of the nonces added on top of `min_nonces`.
+    pub max_extra_nonces: usize,
+    /// How long votes computed against the difficulty before it rose are still accepted.
+    #[serde(default = "default_adaptive_grace_seconds")]
+    pub grace_seconds: u64,
+}
+
+fn default_adaptive_step() -> usize {
+    1
+}
+
+fn default_adaptive_grace_seconds() -> u64 {
+    60
+}
+
+/// Difficulty a vote submitted now has to satisfy.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DifficultyWeb {
+    pub election_id: String,
+    pub prefix: String,
+    pub salt: String,
+    pub min_nonces: usize,
+    pub raised: bool,
+    pub challenge_required: bool,
+}
+
+/// Single-use challenge, the chain of a vote then starts from `uuid + salt + challenge`.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ChallengeWeb {
+    pub election_id: String,
+    pub challenge: String,
+    pub expires: DateTime<Utc>,
+}
+
+impl ElectionWeb {
+    pub fn has_poll(&self, poll_type: PollType) -> bool {
+        self.polls.contains(&poll_type)
+    }
+
+    pub fn is_open(&self, time: DateTime<Utc>) -> bool {
+        self.opens.is_none_or(|opens| opens <= time)
+            && self.closes.is_none_or(|closes| time < closes)
+    }
+
+    pub fn voting_range(&self) -> VoteRange {
+        VoteRange {
+            from: self.opens,
+            to: self.closes,
+        }
+    }
+}
+
+/// Votes submitted in `[from, to)`, a missing bound means no limit.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VoteRange {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// Path of election scoped routes. Legacy routes without the election id use the default election.
+#[derive(Deserialize, Debug)]
+pub struct ElectionPath {
+    pub election_id: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct VotePath {
+    pub election_id: Option<String>,
+    pub uuid: String,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct VoteWeb {
     pub uuid: String,
     pub nonces: Vec<String>,
+    #[serde(default)]
+    pub challenge: Option<String>,
+    pub order: Vec<i32>,
+    pub polls: PollsWeb,
+}
+
+/// Returned for a new vote, the edit token is needed to change the vote later.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AddVoteResultWeb {
+    pub uuid: String,
+    pub edit_token: String,
+    pub receipt: ReceiptWeb,
+}
+
+/// Signed confirmation that a ballot with the given hash was recorded.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ReceiptWeb {
+    pub election_id: String,
+    pub uuid: String,
+    pub ballot_hash: String,
+    pub timestamp: DateTime<Utc>,
+    pub signature: String,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ReceiptStatus {
+    /// The receipt was not issued by this server.
+    InvalidSignature,
+    NotFound,
+    /// The vote is recorded with the ballot of the receipt.
+    Recorded,
+    /// The ballot of the receipt was replaced by the voter later.
+    Superseded,
+    /// The recorded vote does not match the receipt.
+    Modified,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ReceiptVerificationWeb {
+    pub status: ReceiptStatus,
+    /// Revision of the vote the receipt belongs to.
+    pub revision: Option<i32>,
+}
+
+/// New answers of an already submitted vote.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EditVoteWeb {
+    pub uuid: String,
+    pub edit_token: String,
+    pub order: Vec<i32>,
+    pub polls: PollsWeb,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct VoteRevisionWeb {
+    pub revision: i32,
     pub order: Vec<i32>,
     pub polls: PollsWeb,
+    pub submitted: DateTime<Utc>,
 }
 
-#[derive(Deserialize, PostgresMapper, Serialize, Debug)]
+#[derive(PostgresMapper, Debug)]
+#[pg_mapper(table = "votes")]
+pub struct VoteRevisionDB {
+    pub revision: i32,
+    pub permutation: String,
+    pub polls: Json<PollsWeb>,
+    pub submitted: DateTime<Utc>,
+}
+
+/// Latest entry of the ballot log of an election.
+#[derive(PostgresMapper, Serialize, Debug)]
+#[pg_mapper(table = "ballot_log")]
+#[serde(rename_all = "camelCase")]
+pub struct BallotLogHeadWeb {
+    pub length: i64,
+    /// Missing for an empty log.
+    pub head_hash: Option<String>,
+}
+
+/// Ballot log entry, `entryHash = sha256(previousHash + leafHash)`, where `leafHash` is SHA-256 of
+/// the vote serialized as returned by `/get_vote`.
+#[derive(PostgresMapper, Serialize, Debug)]
+#[pg_mapper(table = "ballot_log")]
+#[serde(rename_all = "camelCase")]
+pub struct BallotLogEntryWeb {
+    pub seq: i64,
+    pub revision: i32,
+    pub leaf_hash: String,
+    pub previous_hash: String,
+    pub entry_hash: String,
+}
+
+/// Entries of one vote in the ballot log. Hashing `followingLeafHashes` one by one onto the entry
+/// hash of the first entry gives the head hash.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct InclusionProofWeb {
+    pub election_id: String,
+    pub uuid: String,
+    pub entries: Vec<BallotLogEntryWeb>,
+    pub following_leaf_hashes: Vec<String>,
+    pub head_hash: String,
+}
+
+#[derive(PostgresMapper, Debug)]
 #[pg_mapper(table = "votes")]
 pub struct VoteDB {
     pub id: String,
     pub nonces: String,
+    pub challenge: Option<String>,
     pub permutation: String,
+    pub polls: Json<PollsWeb>,
+}
+
+/// Number of votes with the given proof of work strength.
+#[derive(PostgresMapper, Serialize, Debug)]
+#[pg_mapper(table = "votes")]
+#[serde(rename_all = "camelCase")]
+pub struct StrengthCountWeb {
+    pub strength: i32,
+    pub count: i64,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct StrengthStatsWeb {
+    pub election_id: String,
+    pub vote_count: i64,
+    pub min: Option<i32>,
+    pub max: Option<i32>,
+    pub average: Option<f64>,
+    pub median: Option<i32>,
+    pub distribution: Vec<StrengthCountWeb>,
+}
+
+/// Poll answers of a stored vote with the proof of work strength (number of nonces).
+#[derive(PostgresMapper, Debug)]
+#[pg_mapper(table = "votes")]
+pub struct VotePollsDB {
     pub strength: i32,
-    pub ip_hash: String,
-
-    // Two-Round Poll
-    pub rd2_0: i32,
-    pub rd2_1: i32,
-    pub rd2_2: i32,
-    pub rd2_3: i32,
-    pub rd2_4: i32,
-    pub rd2_5: i32,
-    pub rd2_6: i32,
-    pub rd2_7: i32,
-    pub rd2_8: i32,
-    pub rd2_9: i32,
-
-    // One-Round Poll
-    pub rd1_0: i32,
-    pub rd1_1: i32,
-    pub rd1_2: i32,
-    pub rd1_3: i32,
-    pub rd1_4: i32,
-    pub rd1_5: i32,
-    pub rd1_6: i32,
-    pub rd1_7: i32,
-    pub rd1_8: i32,
-    pub rd1_9: i32,
-
-    // Divide Poll
-    pub div_0: i32,
-    pub div_1: i32,
-    pub div_2: i32,
-    pub div_3: i32,
-    pub div_4: i32,
-    pub div_5: i32,
-    pub div_6: i32,
-    pub div_7: i32,
-    pub div_8: i32,
-    pub div_9: i32,
-
-    // D21 Poll
-    pub d21_0: i32,
-    pub d21_1: i32,
-    pub d21_2: i32,
-    pub d21_3: i32,
-    pub d21_4: i32,
-    pub d21_5: i32,
-    pub d21_6: i32,
-    pub d21_7: i32,
-    pub d21_8: i32,
-    pub d21_9: i32,
-
-    // Doodle Poll
-    pub ddl_0: i32,
-    pub ddl_1: i32,
-    pub ddl_2: i32,
-    pub ddl_3: i32,
-    pub ddl_4: i32,
-    pub ddl_5: i32,
-    pub ddl_6: i32,
-    pub ddl_7: i32,
-    pub ddl_8: i32,
-    pub ddl_9: i32,
-
-    // Ord Poll
-    pub ord_0: i32,
-    pub ord_1: i32,
-    pub ord_2: i32,
-    pub ord_3: i32,
-    pub ord_4: i32,
-    pub ord_5: i32,
-    pub ord_6: i32,
-    pub ord_7: i32,
-    pub ord_8: i32,
-    pub ord_9: i32,
-
-    // Star Poll
-    pub str_0: i32,
-    pub str_1: i32,
-    pub str_2: i32,
-    pub str_3: i32,
-    pub str_4: i32,
-    pub str_5: i32,
-    pub str_6: i32,
-    pub str_7: i32,
-    pub str_8: i32,
-    pub str_9: i32,
-
-    // Emoji Poll
-    pub emj_0: String,
-    pub emj_1: String,
-    pub emj_2: String,
-    pub emj_3: String,
-    pub emj_4: String,
-    pub emj_5: String,
-    pub emj_6: String,
-    pub emj_7: String,
-    pub emj_8: String,
-    pub emj_9: String,
+    pub polls: Json<PollsWeb>,
+}
+
+/// Body of error responses.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorWeb {
+    pub code: String,
+    pub message: String,
+    pub field: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum OrderScoring {
+    #[default]
+    Borda,
+    Dowdall,
+    Custom,
+}
+
+/// How much a vote counts depending on its proof of work strength.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum StrengthWeighting {
+    /// Every vote counts once.
+    #[default]
+    None,
+    /// A vote counts as many times as its strength, at most 32 times.
+    Linear,
+    /// A vote counts `1 + log2(strength)` times, at most 32 times.
+    Log,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum VoteWindow {
+    /// Votes submitted while the election was open.
+    #[default]
+    Valid,
+    All,
+    /// Votes submitted between the `from` and `to` query parameters.
+    Range,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ResultsQuery {
+    pub window: Option<VoteWindow>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub doodle_yes_weight: Option<f64>,
+    pub doodle_if_need_be_weight: Option<f64>,
+    pub order_scoring: Option<OrderScoring>,
+    /// Comma separated weights of the first, second, ... place for the custom order scoring.
+    pub order_weights: Option<String>,
+    /// Votes with fewer nonces are left out.
+    pub min_strength: Option<i32>,
+    /// Counts also votes flagged by the IP limit.
+    pub include_flagged: Option<bool>,
+    pub strength_weighting: Option<StrengthWeighting>,
+}
+
+impl ResultsQuery {
+    pub fn vote_range(&self, election: &ElectionWeb) -> Result<VoteRange, MyError> {
+        vote_range(self.window, self.from, self.to, election)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsQuery {
+    pub window: Option<VoteWindow>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl StatsQuery {
+    pub fn vote_range(&self, election: &ElectionWeb) -> Result<VoteRange, MyError> {
+        vote_range(self.window, self.from, self.to, election)
+    }
+}
+
+/// Range of votes to count, resolved against the election's voting window.
+fn vote_range(
+    window: Option<VoteWindow>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    election: &ElectionWeb,
+) -> Result<VoteRange, MyError> {
+    match window.unwrap_or_default() {
+        VoteWindow::Valid => Result::Ok(election.voting_range()),
+        VoteWindow::All => Result::Ok(VoteRange::default()),
+        VoteWindow::Range => {
+            if let (Some(from), Some(to)) = (from, to) {
+                if from >= to {
+                    return Result::Err(MyError::invalid(
+                        "invalid_range",
+                        "to",
+                        "Invalid vote range.",
+                    ));
+                }
+            }
+            Result::Ok(VoteRange { from, to })
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EmojiCountWeb {
+    pub emoji: String,
+    pub count: i32,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CandidateVotesWeb {
+    pub candidate: i32,
+    pub votes: i32,
+    pub percent: f64,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TwoRoundRunoffWeb {
+    pub first_round: Vec<CandidateVotesWeb>,
+    /// Empty when there are no first round votes.
+    pub second_round: Vec<CandidateVotesWeb>,
+    pub tie_breaking: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CondorcetWeb {
+    /// `pairwise[a][b]` is the number of voters preferring candidate `a` over candidate `b`.
+    pub pairwise: Vec<Vec<i32>>,
+    /// `strongest_paths[a][b]` is the strength of the strongest Schulze path from `a` to `b`.
+    pub strongest_paths: Vec<Vec<i32>>,
+    pub condorcet_winner: Option<i32>,
+    pub schulze_ranking: Vec<i32>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct IrvRoundWeb {
+    pub counts: Vec<CandidateVotesWeb>,
+    pub eliminated: Option<i32>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct IrvWeb {
+    pub rounds: Vec<IrvRoundWeb>,
+    pub winner: Option<i32>,
+    pub tie_breaking: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CandidateScoreWeb {
+    pub candidate: i32,
+    pub score: i32,
+    pub average: f64,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct StarRunoffWeb {
+    pub score_round: Vec<CandidateScoreWeb>,
+    pub runoff: Vec<CandidateVotesWeb>,
+    pub no_preference: i32,
+    pub winner: Option<i32>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct D21CountsWeb {
+    pub plus: i32,
+    pub minus: i32,
+    pub net: i32,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct D21TallyWeb {
+    pub candidates: Vec<D21CountsWeb>,
+    pub minus_voters: i32,
+    pub minus_voters_percent: f64,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DoodleCountsWeb {
+    pub yes: i32,
+    pub if_need_be: i32,
+    pub score: f64,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DoodleTallyWeb {
+    pub candidates: Vec<DoodleCountsWeb>,
+    pub yes_weight: f64,
+    pub if_need_be_weight: f64,
+    /// Candidates ordered by "yes" answers, ties broken by "if need be" answers.
+    pub ranking: Vec<i32>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderScoringWeb {
+    pub method: OrderScoring,
+    /// Points for the first, second, ... place.
+    pub weights: Vec<f64>,
+    pub scores: Vec<f64>,
+    pub ranking: Vec<i32>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MedianScoreWeb {
+    pub median: i32,
+    pub lower_quartile: i32,
+    pub upper_quartile: i32,
+    pub average: f64,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MajorityJudgmentWeb {
+    pub candidates: Vec<MedianScoreWeb>,
+    pub ranking: Vec<i32>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ResultsWeb {
+    pub election_id: String,
+    pub candidates: Vec<CandidateWeb>,
+    /// Counted votes, each weighted vote counts multiple times.
+    pub vote_count: i32,
+    /// Counted ballots, regardless of weighting.
+    pub ballot_count: i32,
+    pub min_strength: Option<i32>,
+    pub strength_weighting: StrengthWeighting,
+    pub two_round: Option<Vec<i32>>,
+    pub two_round_runoff: Option<TwoRoundRunoffWeb>,
+    pub one_round: Option<Vec<i32>>,
+    pub divide: Option<Vec<i32>>,
+    pub d21: Option<D21TallyWeb>,
+    pub doodle: Option<DoodleTallyWeb>,
+    pub order: Option<Vec<i32>>,
+    pub order_scoring: Option<OrderScoringWeb>,
+    pub condorcet: Option<CondorcetWeb>,
+    pub irv: Option<IrvWeb>,
+    pub star: Option<Vec<i32>>,
+    pub star_runoff: Option<StarRunoffWeb>,
+    pub majority_judgment: Option<MajorityJudgmentWeb>,
+    pub emoji: Option<Vec<Vec<EmojiCountWeb>>>,
 }
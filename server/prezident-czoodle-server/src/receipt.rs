@@ -0,0 +1,208 @@
+use crate::crypto_utils::{hmac_sha256, random_hex, sha256, verify_hmac_sha256};
+use crate::models::{PollsWeb, ReceiptStatus, ReceiptVerificationWeb, ReceiptWeb, VoteRevisionWeb};
+use chrono::{SubsecRound, Utc};
+use serde::Serialize;
+
+/// Ballot as it is hashed for receipts, serialized to JSON with a fixed field order.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CanonicalBallot<'a> {
+    election_id: &'a str,
+    uuid: &'a str,
+    order: &'a [i32],
+    polls: &'a PollsWeb,
+}
+
+/// SHA-256 of the canonical JSON of the ballot.
+pub fn ballot_hash(election_id: &str, uuid: &str, order: &[i32], polls: &PollsWeb) -> String {
+    let ballot = CanonicalBallot {
+        election_id,
+        uuid,
+        order,
+        polls,
+    };
+    sha256(&serde_json::to_string(&ballot).unwrap())
+}
+
+/// Signs receipts of recorded votes with HMAC-SHA256.
+#[derive(Debug)]
+pub struct ReceiptSigner {
+    secret: Vec<u8>,
+}
+
+impl ReceiptSigner {
+    /// Uses the configured secret, or a random one when not set (receipts issued before
+    /// a restart can then no longer be verified).
+    pub fn new(secret: Option<&str>) -> Self {
+        let secret = match secret {
+            Some(secret) => secret.as_bytes().to_vec(),
+            None => random_hex(32).into_bytes(),
+        };
+        ReceiptSigner { secret }
+    }
+
+    pub fn issue(
+        &self,
+        election_id: &str,
+        uuid: &str,
+        order: &[i32],
+        polls: &PollsWeb,
+    ) -> ReceiptWeb {
+        let mut receipt = ReceiptWeb {
+            election_id: election_id.to_owned(),
+            uuid: uuid.to_owned(),
+            ballot_hash: ballot_hash(election_id, uuid, order, polls),
+            // Only milliseconds are signed, which survive a round trip through JavaScript dates.
+            timestamp: Utc::now().trunc_subsecs(3),
+            signature: String::new(),
+        };
+        receipt.signature = hmac_sha256(&self.secret, &signed_message(&receipt));
+        receipt
+    }
+
+    pub fn verify(&self, receipt: &ReceiptWeb) -> bool {
+        verify_hmac_sha256(&self.secret, &signed_message(receipt), &receipt.signature)
+    }
+}
+
+fn signed_message(receipt: &ReceiptWeb) -> String {
+    format!(
+        "{}.{}.{}.{}",
+        receipt.election_id,
+        receipt.uuid,
+        receipt.ballot_hash,
+        receipt.timestamp.timestamp_millis()
+    )
+}
+
+/// Compares the ballot hash of a receipt with the recorded revisions of the vote, the oldest first.
+pub fn check_revisions(
+    receipt: &ReceiptWeb,
+    revisions: &[VoteRevisionWeb],
+) -> ReceiptVerificationWeb {
+    let matching = revisions.iter().rev().find(|revision| {
+        ballot_hash(
+            &receipt.election_id,
+            &receipt.uuid,
+            &revision.order,
+            &revision.polls,
+        ) == receipt.ballot_hash
+    });
+    let status = match (revisions.last(), matching) {
+        (None, _) => ReceiptStatus::NotFound,
+        (Some(_), None) => ReceiptStatus::Modified,
+        (Some(current), Some(matching)) if current.revision == matching.revision => {
+            ReceiptStatus::Recorded
+        }
+        (Some(_), Some(_)) => ReceiptStatus::Superseded,
+    };
+    ReceiptVerificationWeb {
+        status,
+        revision: matching.map(|revision| revision.revision),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, SecondsFormat};
+
+    fn order_polls(order: &[i32]) -> PollsWeb {
+        PollsWeb {
+            two_round: None,
+            one_round: None,
+            divide: None,
+            d21: None,
+            doodle: None,
+            order: Some(order.to_vec()),
+            star: None,
+            emoji: None,
+        }
+    }
+
+    fn revision(revision: i32, points: &[i32]) -> VoteRevisionWeb {
+        VoteRevisionWeb {
+            revision,
+            order: vec![0, 1, 2],
+            polls: order_polls(points),
+            submitted: Utc::now(),
+        }
+    }
+
+    fn receipt(signer: &ReceiptSigner, points: &[i32]) -> ReceiptWeb {
+        signer.issue("test", "uuid", &[0, 1, 2], &order_polls(points))
+    }
+
+    #[test]
+    fn receipt_survives_millisecond_round_trip() {
+        let signer = ReceiptSigner::new(Some("secret"));
+        let mut receipt = receipt(&signer, &[3, 2, 1]);
+        assert!(signer.verify(&receipt));
+
+        // A JavaScript client keeps only milliseconds of the timestamp.
+        let timestamp = receipt
+            .timestamp
+            .to_rfc3339_opts(SecondsFormat::Millis, true);
+        receipt.timestamp = timestamp.parse().unwrap();
+        assert!(signer.verify(&receipt));
+    }
+
+    #[test]
+    fn tampered_receipt_has_invalid_signature() {
+        let signer = ReceiptSigner::new(Some("secret"));
+
+        let mut tampered = receipt(&signer, &[3, 2, 1]);
+        tampered.ballot_hash = receipt(&signer, &[1, 2, 3]).ballot_hash;
+        assert!(!signer.verify(&tampered));
+
+        let mut tampered = receipt(&signer, &[3, 2, 1]);
+        tampered.timestamp += Duration::milliseconds(1);
+        assert!(!signer.verify(&tampered));
+
+        let other_signer = ReceiptSigner::new(Some("other secret"));
+        assert!(!other_signer.verify(&receipt(&signer, &[3, 2, 1])));
+    }
+
+    #[test]
+    fn receipt_of_the_current_revision_is_recorded() {
+        let signer = ReceiptSigner::new(None);
+        let revisions = [revision(1, &[1, 2, 3]), revision(2, &[3, 2, 1])];
+
+        let result = check_revisions(&receipt(&signer, &[3, 2, 1]), &revisions);
+
+        assert_eq!(result.status, ReceiptStatus::Recorded);
+        assert_eq!(result.revision, Some(2));
+    }
+
+    #[test]
+    fn receipt_of_an_older_revision_is_superseded() {
+        let signer = ReceiptSigner::new(None);
+        let revisions = [revision(1, &[1, 2, 3]), revision(2, &[3, 2, 1])];
+
+        let result = check_revisions(&receipt(&signer, &[1, 2, 3]), &revisions);
+
+        assert_eq!(result.status, ReceiptStatus::Superseded);
+        assert_eq!(result.revision, Some(1));
+    }
+
+    #[test]
+    fn receipt_of_an_unrecorded_ballot_is_modified() {
+        let signer = ReceiptSigner::new(None);
+        let revisions = [revision(1, &[1, 2, 3])];
+
+        let result = check_revisions(&receipt(&signer, &[2, 2, 2]), &revisions);
+
+        assert_eq!(result.status, ReceiptStatus::Modified);
+        assert_eq!(result.revision, None);
+    }
+
+    #[test]
+    fn receipt_without_revisions_is_not_found() {
+        let signer = ReceiptSigner::new(None);
+
+        let result = check_revisions(&receipt(&signer, &[3, 2, 1]), &[]);
+
+        assert_eq!(result.status, ReceiptStatus::NotFound);
+        assert_eq!(result.revision, None);
+    }
+}
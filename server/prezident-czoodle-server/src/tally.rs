@@ -0,0 +1,999 @@
+use crate::errors::MyError;
+use crate::models::{
+    CandidateScoreWeb, CandidateVotesWeb, CondorcetWeb, D21CountsWeb, D21TallyWeb, DoodleCountsWeb,
+    DoodleTallyWeb, ElectionWeb, EmojiCountWeb, IrvRoundWeb, IrvWeb, MajorityJudgmentWeb,
+    MedianScoreWeb, OrderScoring, OrderScoringWeb, PollType, PollsWeb, ResultsQuery, ResultsWeb,
+    StarRunoffWeb, StrengthCountWeb, StrengthStatsWeb, StrengthWeighting, TwoRoundRunoffWeb,
+    VotePollsDB,
+};
+use std::cmp::{Ordering, Reverse};
+use std::collections::HashMap;
+
+/// Counted vote and the number of times it counts, see `strength_weight`.
+#[derive(Debug, Clone, Copy)]
+struct Ballot<'a> {
+    polls: &'a PollsWeb,
+    weight: i32,
+}
+
+/// Total weight of the ballots matching the predicate.
+fn weight_where(votes: &[Ballot], predicate: impl Fn(&PollsWeb) -> bool) -> i32 {
+    votes
+        .iter()
+        .filter(|v| predicate(v.polls))
+        .map(|v| v.weight)
+        .sum()
+}
+
+/// Counts single-choice polls (two-round, one-round), where each vote holds one candidate index.
+fn count_choices(
+    votes: &[Ballot],
+    candidate_count: usize,
+    choice: fn(&PollsWeb) -> Option<i32>,
+) -> Vec<i32> {
+    let mut totals = vec![0; candidate_count];
+    for vote in votes {
+        if let Some(total) = choice(vote.polls)
+            .and_then(|index| usize::try_from(index).ok())
+            .and_then(|index| totals.get_mut(index))
+        {
+            *total += vote.weight;
+        }
+    }
+    totals
+}
+
+/// Sums per-candidate points of polls, where each vote holds one value per candidate.
+fn sum_points(
+    votes: &[Ballot],
+    candidate_count: usize,
+    points: fn(&PollsWeb) -> &Option<Vec<i32>>,
+) -> Vec<i32> {
+    let mut totals = vec![0; candidate_count];
+    for vote in votes {
+        for (total, value) in totals.iter_mut().zip(points(vote.polls).iter().flatten()) {
+            *total += value * vote.weight;
+        }
+    }
+    totals
+}
+
+/// Ratio of `part` and `total` rounded to two decimal places, zero for an empty `total`.
+fn average(part: i32, total: i32) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        (f64::from(part) * 100.0 / f64::from(total)).round() / 100.0
+    }
+}
+
+/// Plus and minus votes of the D21 poll counted separately for each candidate.
+fn d21(votes: &[Ballot], candidate_count: usize) -> D21TallyWeb {
+    let mut candidates: Vec<D21CountsWeb> = (0..candidate_count)
+        .map(|_| D21CountsWeb {
+            plus: 0,
+            minus: 0,
+            net: 0,
+        })
+        .collect();
+    let mut minus_voters = 0;
+    let mut voters = 0;
+
+    for (d21, weight) in votes
+        .iter()
+        .filter_map(|v| Some((v.polls.d21.as_ref()?, v.weight)))
+    {
+        voters += weight;
+        for (counts, &value) in candidates.iter_mut().zip(d21) {
+            if value > 0 {
+                counts.plus += weight;
+            } else if value < 0 {
+                counts.minus += weight;
+            }
+        }
+        if d21.iter().any(|&value| value < 0) {
+            minus_voters += weight;
+        }
+    }
+    for counts in candidates.iter_mut() {
+        counts.net = counts.plus - counts.minus;
+    }
+
+    D21TallyWeb {
+        candidates,
+        minus_voters,
+        minus_voters_percent: percent(minus_voters, voters),
+    }
+}
+
+const MAX_STRENGTH_WEIGHT: u32 = 32;
+const DOODLE_YES: i32 = 2;
+const DOODLE_IF_NEED_BE: i32 = 1;
+const DEFAULT_DOODLE_YES_WEIGHT: f64 = 1.0;
+const DEFAULT_DOODLE_IF_NEED_BE_WEIGHT: f64 = 0.5;
+
+/// Counts "yes" and "if need be" answers of the Doodle poll separately and combines them into
+/// a weighted score.
+fn doodle(
+    votes: &[Ballot],
+    candidate_count: usize,
+    yes_weight: f64,
+    if_need_be_weight: f64,
+) -> DoodleTallyWeb {
+    let mut counts = vec![(0, 0); candidate_count];
+    for vote in votes {
+        for (count, &value) in counts.iter_mut().zip(vote.polls.doodle.iter().flatten()) {
+            if value == DOODLE_YES {
+                count.0 += vote.weight;
+            } else if value == DOODLE_IF_NEED_BE {
+                count.1 += vote.weight;
+            }
+        }
+    }
+
+    let mut ranking: Vec<usize> = (0..counts.len()).collect();
+    ranking.sort_by_key(|&candidate| Reverse(counts[candidate]));
+
+    DoodleTallyWeb {
+        candidates: counts
+            .into_iter()
+            .map(|(yes, if_need_be)| DoodleCountsWeb {
+                yes,
+                if_need_be,
+                score: f64::from(yes) * yes_weight + f64::from(if_need_be) * if_need_be_weight,
+            })
+            .collect(),
+        yes_weight,
+        if_need_be_weight,
+        ranking: ranking.into_iter().map(|c| c as i32).collect(),
+    }
+}
+
+fn validate_weight(weight: Option<f64>, default: f64, field: &str) -> Result<f64, MyError> {
+    match weight {
+        None => Result::Ok(default),
+        Some(w) if w.is_finite() => Result::Ok(w),
+        Some(_) => Result::Err(MyError::invalid("invalid_weight", field, "Invalid weight.")),
+    }
+}
+
+/// Points for the first, second, ... place of the order poll under the given scoring method.
+fn order_weights(
+    method: OrderScoring,
+    custom: Option<&str>,
+    count: usize,
+) -> Result<Vec<f64>, MyError> {
+    match method {
+        OrderScoring::Borda => Result::Ok((0..count).rev().map(|w| w as f64).collect()),
+        OrderScoring::Dowdall => Result::Ok((1..=count).map(|place| 1.0 / place as f64).collect()),
+        OrderScoring::Custom => {
+            let weights = custom
+                .ok_or_else(|| {
+                    MyError::invalid(
+                        "missing_order_weights",
+                        "orderWeights",
+                        "Missing order weights.",
+                    )
+                })?
+                .split(',')
+                .map(|w| w.trim().parse::<f64>().ok().filter(|w| w.is_finite()))
+                .collect::<Option<Vec<f64>>>()
+                .ok_or_else(|| {
+                    MyError::invalid(
+                        "invalid_order_weights",
+                        "orderWeights",
+                        "Invalid order weights.",
+                    )
+                })?;
+            if weights.len() != count {
+                return Result::Err(MyError::invalid(
+                    "invalid_order_weights",
+                    "orderWeights",
+                    "Invalid number of order weights.",
+                ));
+            }
+            Result::Ok(weights)
+        }
+    }
+}
+
+/// Positional scoring of the order poll. A candidate given `n` points by a voter was ranked at
+/// place `candidate count - n` (counting from zero) and receives the weight of that place.
+fn order_scoring(
+    votes: &[Ballot],
+    candidate_count: usize,
+    method: OrderScoring,
+    weights: Vec<f64>,
+) -> OrderScoringWeb {
+    let mut scores = vec![0.0; candidate_count];
+    for vote in votes {
+        for (score, &points) in scores.iter_mut().zip(vote.polls.order.iter().flatten()) {
+            let place = usize::try_from(candidate_count as i32 - points).ok();
+            if let Some(weight) = place.and_then(|p| weights.get(p)) {
+                *score += weight * f64::from(vote.weight);
+            }
+        }
+    }
+
+    let mut ranking: Vec<usize> = (0..scores.len()).collect();
+    ranking.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+
+    OrderScoringWeb {
+        method,
+        weights,
+        scores: scores
+            .into_iter()
+            .map(|s| (s * 100.0).round() / 100.0)
+            .collect(),
+        ranking: ranking.into_iter().map(|c| c as i32).collect(),
+    }
+}
+
+/// Share of `part` in `total` in percent, rounded to two decimal places.
+fn percent(part: i32, total: i32) -> f64 {
+    average(part * 100, total)
+}
+
+/// Whether the voter gave candidate `a` more points than candidate `b` in the order poll.
+fn prefers(vote: &PollsWeb, a: usize, b: usize) -> bool {
+    let order = vote.order.as_deref().unwrap_or_default();
+    match (order.get(a), order.get(b)) {
+        (Some(points_a), Some(points_b)) => points_a > points_b,
+        _ => false,
+    }
+}
+
+/// Sorts `(candidate, votes)` pairs, the most votes first. Ties keep the lower candidate index first.
+fn sort_by_votes(counts: &mut [(usize, i32)]) {
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+}
+
+fn to_candidate_votes(counts: &[(usize, i32)]) -> Vec<CandidateVotesWeb> {
+    let total: i32 = counts.iter().map(|&(_, votes)| votes).sum();
+    counts
+        .iter()
+        .map(|&(candidate, votes)| CandidateVotesWeb {
+            candidate: candidate as i32,
+            votes,
+            percent: percent(votes, total),
+        })
+        .collect()
+}
+
+const TWO_ROUND_TIE_BREAKING: &str = "Candidates with the same number of first round votes are \
+    ordered by their total order poll points, then by the lower index. A tied second round keeps \
+    the first round order.";
+
+/// Simulates the second round of the two-round poll. The two candidates with the most first-round
+/// votes advance and every voter supports the one of them they ranked higher in the order poll.
+/// Nobody advances when there are no first-round votes.
+fn two_round_runoff(
+    votes: &[Ballot],
+    first_round_totals: &[i32],
+    order_totals: &[i32],
+) -> TwoRoundRunoffWeb {
+    let mut first_round: Vec<(usize, i32)> =
+        first_round_totals.iter().copied().enumerate().collect();
+    first_round.sort_by(|a, b| {
+        b.1.cmp(&a.1)
+            .then_with(|| order_totals.get(b.0).cmp(&order_totals.get(a.0)))
+            .then_with(|| a.0.cmp(&b.0))
+    });
+
+    let any_votes = first_round_totals.iter().any(|&total| total > 0);
+    let mut second_round: Vec<(usize, i32)> = match first_round[..] {
+        [(a, _), (b, _), ..] if any_votes => vec![
+            (a, weight_where(votes, |v| prefers(v, a, b))),
+            (b, weight_where(votes, |v| prefers(v, b, a))),
+        ],
+        _ => vec![],
+    };
+    // Stable sort, so that a tie keeps the first round order.
+    second_round.sort_by_key(|&(_, votes)| Reverse(votes));
+
+    TwoRoundRunoffWeb {
+        first_round: to_candidate_votes(&first_round),
+        second_round: to_candidate_votes(&second_round),
+        tie_breaking: TWO_ROUND_TIE_BREAKING.to_owned(),
+    }
+}
+
+/// Builds the pairwise-preference matrix from the order poll.
+fn pairwise_preferences(votes: &[Ballot], candidate_count: usize) -> Vec<Vec<i32>> {
+    let mut pairwise = vec![vec![0; candidate_count]; candidate_count];
+    for vote in votes {
+        for (a, row) in pairwise.iter_mut().enumerate() {
+            for (b, preferred) in row.iter_mut().enumerate() {
+                if prefers(vote.polls, a, b) {
+                    *preferred += vote.weight;
+                }
+            }
+        }
+    }
+    pairwise
+}
+
+/// Candidate who beats every other candidate in a head-to-head comparison, if there is one.
+fn condorcet_winner(pairwise: &[Vec<i32>]) -> Option<usize> {
+    (0..pairwise.len())
+        .find(|&a| (0..pairwise.len()).all(|b| a == b || pairwise[a][b] > pairwise[b][a]))
+}
+
+/// Strengths of the strongest paths between all pairs of candidates (widest path variant of
+/// Floyd–Warshall), as defined by the Schulze method.
+fn schulze_strongest_paths(pairwise: &[Vec<i32>]) -> Vec<Vec<i32>> {
+    let count = pairwise.len();
+    let mut paths = vec![vec![0; count]; count];
+    for a in 0..count {
+        for b in 0..count {
+            if a != b && pairwise[a][b] > pairwise[b][a] {
+                paths[a][b] = pairwise[a][b];
+            }
+        }
+    }
+    for via in 0..count {
+        for a in 0..count {
+            if a == via {
+                continue;
+            }
+            for b in 0..count {
+                if b == a || b == via {
+                    continue;
+                }
+                paths[a][b] = paths[a][b].max(paths[a][via].min(paths[via][b]));
+            }
+        }
+    }
+    paths
+}
+
+/// Orders candidates by the number of others they defeat through strongest paths. Ties keep the
+/// lower candidate index first.
+fn schulze_ranking(paths: &[Vec<i32>]) -> Vec<usize> {
+    let mut ranking: Vec<(usize, i32)> = (0..paths.len())
+        .map(|a| {
+            let defeated = (0..paths.len())
+                .filter(|&b| paths[a][b] > paths[b][a])
+                .count();
+            (a, defeated as i32)
+        })
+        .collect();
+    sort_by_votes(&mut ranking);
+    ranking
+        .into_iter()
+        .map(|(candidate, _)| candidate)
+        .collect()
+}
+
+fn condorcet(votes: &[Ballot], candidate_count: usize) -> CondorcetWeb {
+    let pairwise = pairwise_preferences(votes, candidate_count);
+    let strongest_paths = schulze_strongest_paths(&pairwise);
+
+    CondorcetWeb {
+        condorcet_winner: condorcet_winner(&pairwise).map(|c| c as i32),
+        schulze_ranking: schulze_ranking(&strongest_paths)
+            .into_iter()
+            .map(|c| c as i32)
+            .collect(),
+        pairwise,
+        strongest_paths,
+    }
+}
+
+const IRV_TIE_BREAKING: &str = "When several candidates share the lowest count, the one with \
+    the fewest total order poll points is eliminated. If they are still tied, the candidate with \
+    the highest index is eliminated.";
+
+/// Active candidate the voter gave the most points in the order poll.
+fn top_active_choice(vote: &PollsWeb, active: &[bool]) -> Option<usize> {
+    vote.order
+        .iter()
+        .flatten()
+        .enumerate()
+        .filter(|&(candidate, _)| active.get(candidate).copied().unwrap_or(false))
+        .max_by_key(|&(_, points)| points)
+        .map(|(candidate, _)| candidate)
+}
+
+/// Instant-runoff voting over the order poll. Every round each ballot counts for its highest
+/// ranked remaining candidate and the weakest candidate is eliminated, until someone gets an
+/// absolute majority of the counted ballots.
+fn irv(votes: &[Ballot], order_totals: &[i32]) -> IrvWeb {
+    let mut active = vec![true; order_totals.len()];
+    let mut rounds = vec![];
+    let mut winner = None;
+
+    while !votes.is_empty() && active.iter().any(|&a| a) {
+        let mut counts: Vec<(usize, i32)> = active
+            .iter()
+            .enumerate()
+            .filter(|&(_, &a)| a)
+            .map(|(candidate, _)| (candidate, 0))
+            .collect();
+        for vote in votes {
+            if let Some(choice) = top_active_choice(vote.polls, &active) {
+                if let Some(count) = counts.iter_mut().find(|c| c.0 == choice) {
+                    count.1 += vote.weight;
+                }
+            }
+        }
+        sort_by_votes(&mut counts);
+
+        let counted: i32 = counts.iter().map(|&(_, votes)| votes).sum();
+        let (leader, leader_votes) = counts[0];
+        if leader_votes * 2 > counted || counts.len() == 1 {
+            winner = Some(leader as i32);
+            rounds.push(IrvRoundWeb {
+                counts: to_candidate_votes(&counts),
+                eliminated: None,
+            });
+            break;
+        }
+
+        let lowest = counts[counts.len() - 1].1;
+        let eliminated = counts
+            .iter()
+            .filter(|&&(_, votes)| votes == lowest)
+            .map(|&(candidate, _)| candidate)
+            .min_by(|&a, &b| {
+                order_totals[a]
+                    .cmp(&order_totals[b])
+                    .then_with(|| b.cmp(&a))
+            })
+            .unwrap_or(counts[counts.len() - 1].0);
+        active[eliminated] = false;
+
+        rounds.push(IrvRoundWeb {
+            counts: to_candidate_votes(&counts),
+            eliminated: Some(eliminated as i32),
+        });
+    }
+
+    IrvWeb {
+        rounds,
+        winner,
+        tie_breaking: IRV_TIE_BREAKING.to_owned(),
+    }
+}
+
+/// Score Then Automatic Runoff over the star poll. The two candidates with the highest total score
+/// advance and each ballot supports the one of them it scored higher. A tied runoff is won by the
+/// candidate with the higher total score.
+fn star_runoff(votes: &[Ballot], star_totals: &[i32]) -> StarRunoffWeb {
+    let mut score_round: Vec<(usize, i32)> = star_totals.iter().copied().enumerate().collect();
+    sort_by_votes(&mut score_round);
+
+    let voter_count = weight_where(votes, |v| v.star.is_some());
+    let scores = score_round
+        .iter()
+        .map(|&(candidate, score)| CandidateScoreWeb {
+            candidate: candidate as i32,
+            score,
+            average: average(score, voter_count),
+        })
+        .collect();
+
+    let (runoff, no_preference, winner) = match score_round[..] {
+        [(a, _), (b, _), ..] if voter_count > 0 => {
+            let score = |vote: &PollsWeb, candidate: usize| {
+                vote.star.as_ref().and_then(|s| s.get(candidate)).copied()
+            };
+            let a_votes = weight_where(votes, |v| score(v, a) > score(v, b));
+            let b_votes = weight_where(votes, |v| score(v, b) > score(v, a));
+            // Sorting keeps `a` first on a tie, which is the candidate with the higher score.
+            let mut runoff = vec![(a, a_votes), (b, b_votes)];
+            runoff.sort_by_key(|&(_, votes)| Reverse(votes));
+            let winner = runoff[0].0 as i32;
+            (runoff, voter_count - a_votes - b_votes, Some(winner))
+        }
+        _ => (vec![], 0, None),
+    };
+
+    StarRunoffWeb {
+        score_round: scores,
+        runoff: to_candidate_votes(&runoff),
+        no_preference,
+        winner,
+    }
+}
+
+/// Sorted scores with their number of occurrences, merging equal scores into one run.
+fn to_runs(mut scores: Vec<(i32, i32)>) -> Vec<(i32, i32)> {
+    scores.sort();
+    let mut runs: Vec<(i32, i32)> = Vec::new();
+    for (score, count) in scores {
+        match runs.last_mut() {
+            Some(last) if last.0 == score => last.1 += count,
+            _ if count > 0 => runs.push((score, count)),
+            _ => {}
+        }
+    }
+    runs
+}
+
+/// Score at the given fraction of the sorted scores, taking the lower one between two ballots.
+fn quantile(runs: &[(i32, i32)], fraction: f64) -> i32 {
+    let total: i32 = runs.iter().map(|&(_, count)| count).sum();
+    if total == 0 {
+        return 0;
+    }
+    let mut position = ((total - 1) as f64 * fraction).floor() as i32;
+    for &(score, count) in runs {
+        if position < count {
+            return score;
+        }
+        position -= count;
+    }
+    0
+}
+
+/// Runs covering the positions `from..to` of the sorted scores.
+fn slice_runs(runs: &[(i32, i32)], from: i32, to: i32) -> Vec<(i32, i32)> {
+    let mut start = 0;
+    let mut sliced = Vec::new();
+    for &(score, count) in runs {
+        let end = start + count;
+        let len = end.min(to) - start.max(from);
+        if len > 0 {
+            sliced.push((score, len));
+        }
+        start = end;
+    }
+    sliced
+}
+
+/// Sequence of medians obtained by repeatedly removing the (lower) median score. Comparing these
+/// sequences lexicographically is the Majority Judgment tie-breaking rule.
+///
+/// The removed medians take turns between the lower half of the sorted scores read downwards and
+/// the upper half read upwards, starting with the lower half for an even number of scores. The
+/// sequence is therefore returned as runs of `(first, second)` pairs of the two halves, which keeps
+/// it as short as the runs of scores instead of the number of ballots.
+fn majority_values(runs: &[(i32, i32)]) -> Vec<((i32, Option<i32>), i32)> {
+    let total: i32 = runs.iter().map(|&(_, count)| count).sum();
+    let mut lower = slice_runs(runs, 0, total / 2);
+    lower.reverse();
+    let upper = slice_runs(runs, total / 2, total);
+    let (first, second) = if total % 2 == 0 {
+        (lower, upper)
+    } else {
+        (upper, lower)
+    };
+
+    let mut values = Vec::new();
+    let mut second = second.into_iter();
+    let mut paired = second.next();
+    for (score, mut count) in first {
+        while count > 0 {
+            match paired {
+                Some((other, other_count)) => {
+                    let len = count.min(other_count);
+                    values.push(((score, Some(other)), len));
+                    count -= len;
+                    paired = if other_count > len {
+                        Some((other, other_count - len))
+                    } else {
+                        second.next()
+                    };
+                }
+                None => {
+                    values.push(((score, None), count));
+                    count = 0;
+                }
+            }
+        }
+    }
+    values
+}
+
+/// Compares two run-length encoded sequences as if they were expanded.
+fn cmp_runs<T: Ord>(a: &[(T, i32)], b: &[(T, i32)]) -> Ordering {
+    let mut a_runs = a.iter().map(|(value, count)| (value, *count));
+    let mut b_runs = b.iter().map(|(value, count)| (value, *count));
+    let (mut a_run, mut b_run) = (a_runs.next(), b_runs.next());
+    loop {
+        match (a_run, b_run) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some((a_value, a_count)), Some((b_value, b_count))) => {
+                if a_value != b_value {
+                    return a_value.cmp(b_value);
+                }
+                let len = a_count.min(b_count);
+                a_run = if a_count > len {
+                    Some((a_value, a_count - len))
+                } else {
+                    a_runs.next()
+                };
+                b_run = if b_count > len {
+                    Some((b_value, b_count - len))
+                } else {
+                    b_runs.next()
+                };
+            }
+        }
+    }
+}
+
+/// Majority Judgment over the star poll, ranking candidates by their median score.
+fn majority_judgment(votes: &[Ballot], candidate_count: usize) -> MajorityJudgmentWeb {
+    let scores: Vec<Vec<(i32, i32)>> = (0..candidate_count)
+        .map(|candidate| {
+            to_runs(
+                votes
+                    .iter()
+                    .filter_map(|v| Some((*v.polls.star.as_ref()?.get(candidate)?, v.weight)))
+                    .collect(),
+            )
+        })
+        .collect();
+
+    let values: Vec<_> = scores.iter().map(|s| majority_values(s)).collect();
+    let mut ranking: Vec<usize> = (0..scores.len()).collect();
+    ranking.sort_by(|&a, &b| cmp_runs(&values[b], &values[a]));
+
+    MajorityJudgmentWeb {
+        candidates: scores
+            .iter()
+            .map(|s| MedianScoreWeb {
+                median: quantile(s, 0.5),
+                lower_quartile: quantile(s, 0.25),
+                upper_quartile: quantile(s, 0.75),
+                average: average(
+                    s.iter().map(|&(score, count)| score * count).sum(),
+                    s.iter().map(|&(_, count)| count).sum(),
+                ),
+            })
+            .collect(),
+        ranking: ranking.into_iter().map(|c| c as i32).collect(),
+    }
+}
+
+/// Counts emoji used for each candidate, the most frequent first. Empty answers are skipped.
+fn count_emoji(votes: &[Ballot], candidate_count: usize) -> Vec<Vec<EmojiCountWeb>> {
+    let mut counts: Vec<HashMap<&str, i32>> = vec![HashMap::new(); candidate_count];
+    for vote in votes {
+        for (candidate_counts, emoji) in counts.iter_mut().zip(vote.polls.emoji.iter().flatten()) {
+            let emoji = emoji.trim();
+            if !emoji.is_empty() {
+                *candidate_counts.entry(emoji).or_insert(0) += vote.weight;
+            }
+        }
+    }
+
+    counts
+        .into_iter()
+        .map(|candidate_counts| {
+            let mut sorted: Vec<EmojiCountWeb> = candidate_counts
+                .into_iter()
+                .map(|(emoji, count)| EmojiCountWeb {
+                    emoji: emoji.to_owned(),
+                    count,
+                })
+                .collect();
+            sorted.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.emoji.cmp(&b.emoji)));
+            sorted
+        })
+        .collect()
+}
+
+/// Summary of the proof of work strength distribution, sorted by strength.
+pub fn strength_stats(election_id: &str, distribution: Vec<StrengthCountWeb>) -> StrengthStatsWeb {
+    let vote_count: i64 = distribution.iter().map(|c| c.count).sum();
+    let total: i64 = distribution
+        .iter()
+        .map(|c| i64::from(c.strength) * c.count)
+        .sum();
+    let mut seen = 0;
+    let median = distribution
+        .iter()
+        .find(|c| {
+            seen += c.count;
+            seen * 2 > vote_count
+        })
+        .map(|c| c.strength);
+    StrengthStatsWeb {
+        election_id: election_id.to_owned(),
+        vote_count,
+        min: distribution.first().map(|c| c.strength),
+        max: distribution.last().map(|c| c.strength),
+        average: (vote_count > 0)
+            .then(|| (total as f64 * 100.0 / vote_count as f64).round() / 100.0),
+        median,
+        distribution,
+    }
+}
+
+/// Number of times a vote of the given proof of work strength is counted.
+fn strength_weight(strength: i32, weighting: StrengthWeighting) -> i32 {
+    let strength = strength.max(1) as u32;
+    let weight = match weighting {
+        StrengthWeighting::None => 1,
+        StrengthWeighting::Linear => strength,
+        StrengthWeighting::Log => 1 + strength.ilog2(),
+    };
+    weight.min(MAX_STRENGTH_WEIGHT) as i32
+}
+
+pub fn tally(
+    stored_votes: &[VotePollsDB],
+    election: &ElectionWeb,
+    query: &ResultsQuery,
+) -> Result<ResultsWeb, MyError> {
+    let count = election.candidates.len();
+    let min_strength = query.min_strength.unwrap_or(0);
+    let strength_weighting = query.strength_weighting.unwrap_or_default();
+    // Weights are whole numbers, so that all tallies stay in whole votes.
+    let ballots: Vec<Ballot> = stored_votes
+        .iter()
+        .filter(|v| v.strength >= min_strength)
+        .map(|v| Ballot {
+            polls: &v.polls.0,
+            weight: strength_weight(v.strength, strength_weighting),
+        })
+        .collect();
+    let votes = ballots.as_slice();
+
+    let doodle_yes_weight = validate_weight(
+        query.doodle_yes_weight,
+        DEFAULT_DOODLE_YES_WEIGHT,
+        "doodleYesWeight",
+    )?;
+    let doodle_if_need_be_weight = validate_weight(
+        query.doodle_if_need_be_weight,
+        DEFAULT_DOODLE_IF_NEED_BE_WEIGHT,
+        "doodleIfNeedBeWeight",
+    )?;
+    let order_scoring_method = query.order_scoring.unwrap_or_default();
+    let order_weights = order_weights(order_scoring_method, query.order_weights.as_deref(), count)?;
+
+    let enabled = |poll_type| election.has_poll(poll_type);
+    let two_round =
+        enabled(PollType::TwoRound).then(|| count_choices(votes, count, |p| p.two_round));
+    let order = enabled(PollType::Order).then(|| sum_points(votes, count, |p| &p.order));
+    let star = enabled(PollType::Star).then(|| sum_points(votes, count, |p| &p.star));
+
+    Result::Ok(ResultsWeb {
+        election_id: election.id.to_owned(),
+        candidates: election.candidates.to_vec(),
+        vote_count: votes.iter().map(|v| v.weight).sum(),
+        ballot_count: votes.len() as i32,
+        min_strength: query.min_strength,
+        strength_weighting,
+        two_round_runoff: two_round
+            .as_ref()
+            .zip(order.as_ref())
+            .map(|(totals, order_totals)| two_round_runoff(votes, totals, order_totals)),
+        two_round,
+        one_round: enabled(PollType::OneRound)
+            .then(|| count_choices(votes, count, |p| p.one_round)),
+        divide: enabled(PollType::Divide).then(|| sum_points(votes, count, |p| &p.divide)),
+        d21: enabled(PollType::D21).then(|| d21(votes, count)),
+        doodle: enabled(PollType::Doodle)
+            .then(|| doodle(votes, count, doodle_yes_weight, doodle_if_need_be_weight)),
+        order_scoring: order
+            .as_ref()
+            .map(|_| order_scoring(votes, count, order_scoring_method, order_weights)),
+        condorcet: order.as_ref().map(|_| condorcet(votes, count)),
+        irv: order.as_ref().map(|totals| irv(votes, totals)),
+        order,
+        star_runoff: star.as_ref().map(|totals| star_runoff(votes, totals)),
+        majority_judgment: star.as_ref().map(|_| majority_judgment(votes, count)),
+        star,
+        emoji: enabled(PollType::Emoji).then(|| count_emoji(votes, count)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_polls() -> PollsWeb {
+        PollsWeb {
+            two_round: None,
+            one_round: None,
+            divide: None,
+            d21: None,
+            doodle: None,
+            order: None,
+            star: None,
+            emoji: None,
+        }
+    }
+
+    /// Order poll answer giving the candidates the listed points, the most for the favourite.
+    fn order_poll(points: &[i32]) -> PollsWeb {
+        PollsWeb {
+            order: Some(points.to_vec()),
+            ..no_polls()
+        }
+    }
+
+    fn star_poll(scores: &[i32]) -> PollsWeb {
+        PollsWeb {
+            star: Some(scores.to_vec()),
+            ..no_polls()
+        }
+    }
+
+    fn ballots(polls: &[PollsWeb]) -> Vec<Ballot<'_>> {
+        polls
+            .iter()
+            .map(|polls| Ballot { polls, weight: 1 })
+            .collect()
+    }
+
+    #[test]
+    fn schulze_resolves_condorcet_cycle() {
+        // 4 × A > B > C, 3 × B > C > A, 2 × C > A > B: A beats B, B beats C and C beats A.
+        let mut polls = vec![];
+        polls.extend((0..4).map(|_| order_poll(&[3, 2, 1])));
+        polls.extend((0..3).map(|_| order_poll(&[1, 3, 2])));
+        polls.extend((0..2).map(|_| order_poll(&[2, 1, 3])));
+
+        let result = condorcet(&ballots(&polls), 3);
+
+        assert_eq!(
+            result.pairwise,
+            vec![vec![0, 6, 4], vec![3, 0, 7], vec![5, 2, 0]]
+        );
+        assert_eq!(result.condorcet_winner, None);
+        assert_eq!(
+            result.strongest_paths,
+            vec![vec![0, 6, 6], vec![5, 0, 7], vec![5, 5, 0]]
+        );
+        assert_eq!(result.schulze_ranking, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn condorcet_winner_beats_everyone() {
+        let polls = vec![
+            order_poll(&[2, 3, 1]),
+            order_poll(&[1, 3, 2]),
+            order_poll(&[3, 2, 1]),
+        ];
+
+        let result = condorcet(&ballots(&polls), 3);
+
+        assert_eq!(result.condorcet_winner, Some(1));
+        assert_eq!(result.schulze_ranking, vec![1, 0, 2]);
+    }
+
+    #[test]
+    fn irv_breaks_ties_for_the_lowest_count() {
+        // First choices A: 2, B: 1, C: 1. Total order points A: 9, B: 9, C: 6.
+        let polls = vec![
+            order_poll(&[3, 2, 1]),
+            order_poll(&[3, 2, 1]),
+            order_poll(&[2, 3, 1]),
+            order_poll(&[1, 2, 3]),
+        ];
+        let votes = ballots(&polls);
+        let order_totals = sum_points(&votes, 3, |p| &p.order);
+
+        let result = irv(&votes, &order_totals);
+
+        let eliminated: Vec<Option<i32>> = result.rounds.iter().map(|r| r.eliminated).collect();
+        // B and C tie with one vote, C has fewer order points. Then A and B tie with two votes
+        // and the same points, the higher index is eliminated.
+        assert_eq!(eliminated, vec![Some(2), Some(1), None]);
+        assert_eq!(result.winner, Some(0));
+        assert_eq!(result.rounds[2].counts[0].votes, 4);
+    }
+
+    #[test]
+    fn irv_without_votes_has_no_winner() {
+        let result = irv(&[], &[0, 0, 0]);
+
+        assert!(result.rounds.is_empty());
+        assert_eq!(result.winner, None);
+    }
+
+    #[test]
+    fn star_runoff_tie_goes_to_the_higher_score() {
+        // Candidate 1 has the higher total score, each finalist is preferred on one ballot.
+        let polls = vec![star_poll(&[0, 100, 0]), star_poll(&[60, 0, 0])];
+        let votes = ballots(&polls);
+        let star_totals = sum_points(&votes, 3, |p| &p.star);
+
+        let result = star_runoff(&votes, &star_totals);
+
+        let runoff: Vec<(i32, i32)> = result
+            .runoff
+            .iter()
+            .map(|c| (c.candidate, c.votes))
+            .collect();
+        assert_eq!(runoff, vec![(1, 1), (0, 1)]);
+        assert_eq!(result.no_preference, 0);
+        assert_eq!(result.winner, Some(1));
+    }
+
+    #[test]
+    fn star_runoff_counts_ballots_without_preference() {
+        let polls = vec![
+            star_poll(&[80, 80, 0]),
+            star_poll(&[100, 20, 0]),
+            star_poll(&[0, 40, 0]),
+            star_poll(&[70, 10, 0]),
+        ];
+        let votes = ballots(&polls);
+        let star_totals = sum_points(&votes, 3, |p| &p.star);
+
+        let result = star_runoff(&votes, &star_totals);
+
+        assert_eq!(result.score_round[0].score, 250);
+        assert_eq!(result.score_round[0].average, 62.5);
+        assert_eq!(result.no_preference, 1);
+        assert_eq!(result.runoff[0].votes, 2);
+        assert_eq!(result.winner, Some(0));
+    }
+
+    #[test]
+    fn star_runoff_without_ballots_has_no_winner() {
+        let polls = vec![order_poll(&[1, 2, 3])];
+        let votes = ballots(&polls);
+        let star_totals = sum_points(&votes, 3, |p| &p.star);
+
+        let result = star_runoff(&votes, &star_totals);
+
+        assert_eq!(result.score_round.len(), 3);
+        assert!(result.runoff.is_empty());
+        assert_eq!(result.no_preference, 0);
+        assert_eq!(result.winner, None);
+    }
+
+    #[test]
+    fn majority_values_alternate_around_the_median() {
+        let runs = to_runs(vec![(90, 1), (20, 1), (50, 2), (70, 1)]);
+        assert_eq!(runs, vec![(20, 1), (50, 2), (70, 1), (90, 1)]);
+
+        // 20 50 50 70 90: medians 50, 50, 70, 20, 90.
+        assert_eq!(
+            majority_values(&runs),
+            vec![((50, Some(50)), 1), ((70, Some(20)), 1), ((90, None), 1)]
+        );
+        assert_eq!(quantile(&runs, 0.25), 50);
+        assert_eq!(quantile(&runs, 0.5), 50);
+        assert_eq!(quantile(&runs, 0.75), 70);
+        assert_eq!(quantile(&[], 0.5), 0);
+    }
+
+    #[test]
+    fn majority_judgment_breaks_median_tie() {
+        // Both candidates have the median 50. Removing it leaves 20 for candidate 0 and 40 for
+        // candidate 1, which wins the tie.
+        let polls = vec![
+            star_poll(&[20, 40]),
+            star_poll(&[50, 50]),
+            star_poll(&[90, 60]),
+        ];
+
+        let result = majority_judgment(&ballots(&polls), 2);
+
+        assert_eq!(result.candidates[0].median, 50);
+        assert_eq!(result.candidates[1].median, 50);
+        assert_eq!(result.ranking, vec![1, 0]);
+    }
+
+    #[test]
+    fn majority_judgment_weights_match_repeated_ballots() {
+        let polls = [star_poll(&[20, 40]), star_poll(&[90, 60])];
+        let ballot = |index: usize, weight: i32| Ballot {
+            polls: &polls[index],
+            weight,
+        };
+        let weighted = vec![ballot(0, 1), ballot(1, 3)];
+        let repeated = vec![ballot(0, 1), ballot(1, 1), ballot(1, 1), ballot(1, 1)];
+
+        let weighted = majority_judgment(&weighted, 2);
+        let repeated = majority_judgment(&repeated, 2);
+
+        assert_eq!(weighted.ranking, vec![0, 1]);
+        assert_eq!(weighted.ranking, repeated.ranking);
+        assert_eq!(weighted.candidates[0].median, 90);
+        assert_eq!(weighted.candidates[0].lower_quartile, 20);
+        assert_eq!(weighted.candidates[0].average, 72.5);
+        assert_eq!(weighted.candidates[1].median, repeated.candidates[1].median);
+    }
+}
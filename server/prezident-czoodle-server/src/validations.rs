@@ -1,208 +1,434 @@
+use crate::challenge::ChallengeStore;
 use crate::crypto_utils::sha256;
 use crate::errors;
 use crate::models;
-
-const CANDIDATE_COUNT: i32 = 10;
+use emojis::SkinTone;
+use unicode_segmentation::UnicodeSegmentation;
 
 fn validate_uuid(vote: &models::VoteWeb) -> Result<(), errors::MyError> {
     if vote.uuid.chars().count() != 36 {
-        return Result::Err(errors::MyError::ValidationError("Invalid UUID".to_owned()));
+        return Result::Err(errors::MyError::invalid(
+            "invalid_uuid",
+            "uuid",
+            "Invalid UUID.",
+        ));
     }
-    return Result::Ok(());
+    Result::Ok(())
 }
 
-fn validate_nonces(vote: &models::VoteWeb) -> Result<(), errors::MyError> {
-    if vote.nonces.len() < 5 {
-        return Result::Err(errors::MyError::ValidationError(
-            "Validation invalid - not enough nonces.".to_owned(),
+fn validate_nonces(
+    vote: &models::VoteWeb,
+    election: &models::ElectionWeb,
+    difficulty: &models::DifficultyWeb,
+    challenges: &ChallengeStore,
+) -> Result<(), errors::MyError> {
+    if vote.nonces.len() < difficulty.min_nonces {
+        return Result::Err(errors::MyError::invalid(
+            "not_enough_nonces",
+            "nonces",
+            "Validation invalid - not enough nonces.",
+        ));
+    }
+    if difficulty.challenge_required && vote.challenge.is_none() {
+        return Result::Err(errors::MyError::invalid(
+            "missing_challenge",
+            "challenge",
+            "Missing challenge.",
         ));
     }
-    let mut current_string = format!("{}{}", vote.uuid, "czoodle");
+    let challenge = vote.challenge.as_deref().unwrap_or("");
+    let mut current_string = format!("{}{}{}", vote.uuid, difficulty.salt, challenge);
     for nonce in &vote.nonces {
         current_string = sha256(&format!("{}{}", current_string, nonce));
-        if !current_string.starts_with("777") {
-            return Result::Err(errors::MyError::ValidationError(
-                "Invalid validation nonce.".to_owned(),
+        if !current_string.starts_with(&difficulty.prefix) {
+            return Result::Err(errors::MyError::invalid(
+                "invalid_nonce",
+                "nonces",
+                "Invalid validation nonce.",
             ));
         }
     }
-    return Result::Ok(());
+    if let Some(challenge) = &vote.challenge {
+        challenges.check(election, challenge)?;
+    }
+    Result::Ok(())
 }
 
-fn validate_order(vote: &models::VoteWeb) -> Result<(), errors::MyError> {
-    if vote.order.len() as i32 != CANDIDATE_COUNT {
-        return Result::Err(errors::MyError::ValidationError(
-            "Invalid length of order array.".to_owned(),
+fn validate_order(order: &[i32], candidate_count: i32) -> Result<(), errors::MyError> {
+    if order.len() as i32 != candidate_count {
+        return Result::Err(errors::MyError::invalid(
+            "invalid_order",
+            "order",
+            "Invalid length of order array.",
         ));
     }
-    let mut sorted = vote.order.clone();
+    let mut sorted = order.to_vec();
     sorted.sort();
 
     for (index, value) in sorted.into_iter().enumerate() {
         if index as i32 != value {
-            return Result::Err(errors::MyError::ValidationError(
-                "Invalid order array.".to_owned(),
+            return Result::Err(errors::MyError::invalid(
+                "invalid_order",
+                "order",
+                "Invalid order array.",
             ));
         }
     }
 
-    return Result::Ok(());
+    Result::Ok(())
 }
 
-fn validate_two_round_poll(vote: &models::VoteWeb) -> Result<(), errors::MyError> {
-    if vote.polls.two_round < 0 || vote.polls.two_round >= CANDIDATE_COUNT {
-        return Result::Err(errors::MyError::ValidationError(
-            "Invalid two-round poll value.".to_owned(),
+fn validate_two_round_poll(two_round: i32, candidate_count: i32) -> Result<(), errors::MyError> {
+    if two_round < 0 || two_round >= candidate_count {
+        return Result::Err(errors::MyError::invalid(
+            "invalid_poll",
+            "polls.twoRound",
+            "Invalid two-round poll value.",
         ));
     }
-    return Result::Ok(());
+    Result::Ok(())
 }
 
-fn validate_one_round_poll(vote: &models::VoteWeb) -> Result<(), errors::MyError> {
-    if vote.polls.one_round < 0 || vote.polls.one_round >= CANDIDATE_COUNT {
-        return Result::Err(errors::MyError::ValidationError(
-            "Invalid one-round poll value.".to_owned(),
+fn validate_one_round_poll(one_round: i32, candidate_count: i32) -> Result<(), errors::MyError> {
+    if one_round < 0 || one_round >= candidate_count {
+        return Result::Err(errors::MyError::invalid(
+            "invalid_poll",
+            "polls.oneRound",
+            "Invalid one-round poll value.",
         ));
     }
-    return Result::Ok(());
+    Result::Ok(())
 }
 
-fn validate_divide_poll(vote: &models::VoteWeb) -> Result<(), errors::MyError> {
-    if vote.polls.divide.len() as i32 != CANDIDATE_COUNT {
-        return Result::Err(errors::MyError::ValidationError(
-            "Invalid length of divide poll array.".to_owned(),
+fn validate_divide_poll(divide: &[i32], candidate_count: i32) -> Result<(), errors::MyError> {
+    if divide.len() as i32 != candidate_count {
+        return Result::Err(errors::MyError::invalid(
+            "invalid_poll",
+            "polls.divide",
+            "Invalid length of divide poll array.",
         ));
     }
-    let sum: i32 = vote.polls.divide.iter().sum();
+    let sum: i32 = divide.iter().sum();
     if sum != 5 {
-        return Result::Err(errors::MyError::ValidationError(
-            "Invalid divide poll value.".to_owned(),
+        return Result::Err(errors::MyError::invalid(
+            "invalid_poll",
+            "polls.divide",
+            "Invalid divide poll value.",
         ));
     }
-    return Result::Ok(());
+    Result::Ok(())
 }
 
-fn validate_d21_poll(vote: &models::VoteWeb) -> Result<(), errors::MyError> {
-    if vote.polls.d21.len() as i32 != CANDIDATE_COUNT {
-        return Result::Err(errors::MyError::ValidationError(
-            "Invalid length of D21 poll array.".to_owned(),
+fn validate_d21_poll(d21: &[i32], candidate_count: i32) -> Result<(), errors::MyError> {
+    if d21.len() as i32 != candidate_count {
+        return Result::Err(errors::MyError::invalid(
+            "invalid_poll",
+            "polls.d21",
+            "Invalid length of D21 poll array.",
         ));
     }
-    let all_valid = vote.polls.d21.iter().all(|&v| v == 0 || v == 1 || v == -1);
+    let all_valid = d21.iter().all(|&v| v == 0 || v == 1 || v == -1);
     if !all_valid {
-        return Result::Err(errors::MyError::ValidationError(
-            "Invalid values in D21 poll.".to_owned(),
+        return Result::Err(errors::MyError::invalid(
+            "invalid_poll",
+            "polls.d21",
+            "Invalid values in D21 poll.",
         ));
     }
 
-    let positive_count = vote.polls.d21.iter().copied().filter(|v| *v > 0).count();
+    let positive_count = d21.iter().copied().filter(|v| *v > 0).count();
 
     if positive_count == 0 {
-        return Result::Err(errors::MyError::ValidationError(
-            "Invalid values in D21 poll - no positive vote.".to_owned(),
+        return Result::Err(errors::MyError::invalid(
+            "invalid_poll",
+            "polls.d21",
+            "Invalid values in D21 poll - no positive vote.",
         ));
     }
 
     if positive_count > 3 {
-        return Result::Err(errors::MyError::ValidationError(
-            "Invalid values in D21 poll - too many positive votes.".to_owned(),
+        return Result::Err(errors::MyError::invalid(
+            "invalid_poll",
+            "polls.d21",
+            "Invalid values in D21 poll - too many positive votes.",
         ));
     }
 
-    let negative_count = vote.polls.d21.iter().copied().filter(|v| *v < 0).count();
+    let negative_count = d21.iter().copied().filter(|v| *v < 0).count();
 
     if negative_count > 1 || (positive_count < 2 && negative_count > 0) {
-        return Result::Err(errors::MyError::ValidationError(
-            "Invalid values in D21 poll - too many negative votes.".to_owned(),
+        return Result::Err(errors::MyError::invalid(
+            "invalid_poll",
+            "polls.d21",
+            "Invalid values in D21 poll - too many negative votes.",
         ));
     }
 
-    return Result::Ok(());
+    Result::Ok(())
 }
 
-fn validate_doodle_poll(vote: &models::VoteWeb) -> Result<(), errors::MyError> {
-    if vote.polls.doodle.len() as i32 != CANDIDATE_COUNT {
-        return Result::Err(errors::MyError::ValidationError(
-            "Invalid length of Doodle poll array.".to_owned(),
+fn validate_doodle_poll(doodle: &[i32], candidate_count: i32) -> Result<(), errors::MyError> {
+    if doodle.len() as i32 != candidate_count {
+        return Result::Err(errors::MyError::invalid(
+            "invalid_poll",
+            "polls.doodle",
+            "Invalid length of Doodle poll array.",
         ));
     }
 
-    let all_valid = vote
-        .polls
-        .doodle
-        .iter()
-        .all(|&v| v == 0 || v == 1 || v == 2);
+    let all_valid = doodle.iter().all(|&v| v == 0 || v == 1 || v == 2);
     if !all_valid {
-        return Result::Err(errors::MyError::ValidationError(
-            "Invalid values in Doodle poll.".to_owned(),
+        return Result::Err(errors::MyError::invalid(
+            "invalid_poll",
+            "polls.doodle",
+            "Invalid values in Doodle poll.",
         ));
     }
 
-    let positive_count = vote.polls.doodle.iter().copied().filter(|v| *v > 0).count();
+    let positive_count = doodle.iter().copied().filter(|v| *v > 0).count();
 
     if positive_count == 0 {
-        return Result::Err(errors::MyError::ValidationError(
-            "Invalid values in Doodle poll - no positive vote".to_owned(),
+        return Result::Err(errors::MyError::invalid(
+            "invalid_poll",
+            "polls.doodle",
+            "Invalid values in Doodle poll - no positive vote.",
         ));
     }
 
-    return Result::Ok(());
+    Result::Ok(())
 }
 
-fn validate_order_poll(vote: &models::VoteWeb) -> Result<(), errors::MyError> {
-    if vote.polls.order.len() as i32 != CANDIDATE_COUNT {
-        return Result::Err(errors::MyError::ValidationError(
-            "Invalid length of order poll array.".to_owned(),
+fn validate_order_poll(order: &[i32], candidate_count: i32) -> Result<(), errors::MyError> {
+    if order.len() as i32 != candidate_count {
+        return Result::Err(errors::MyError::invalid(
+            "invalid_poll",
+            "polls.order",
+            "Invalid length of order poll array.",
         ));
     }
-    let mut sorted = vote.polls.order.clone();
+    let mut sorted = order.to_vec();
     sorted.sort();
 
     for (index, value) in sorted.into_iter().enumerate() {
         if index as i32 != value - 1 {
-            return Result::Err(errors::MyError::ValidationError(
-                "Invalid order poll.".to_owned(),
+            return Result::Err(errors::MyError::invalid(
+                "invalid_poll",
+                "polls.order",
+                "Invalid order poll.",
             ));
         }
     }
-    return Result::Ok(());
+    Result::Ok(())
 }
 
-fn validate_star_poll(vote: &models::VoteWeb) -> Result<(), errors::MyError> {
-    if vote.polls.star.len() as i32 != CANDIDATE_COUNT {
-        return Result::Err(errors::MyError::ValidationError(
-            "Invalid length of star poll array.".to_owned(),
+fn validate_star_poll(star: &[i32], candidate_count: i32) -> Result<(), errors::MyError> {
+    if star.len() as i32 != candidate_count {
+        return Result::Err(errors::MyError::invalid(
+            "invalid_poll",
+            "polls.star",
+            "Invalid length of star poll array.",
         ));
     }
 
-    let all_valid = vote.polls.star.iter().all(|&v| v >= 0 && v <= 100);
+    let all_valid = star.iter().all(|&v| (0..=100).contains(&v));
     if !all_valid {
-        return Result::Err(errors::MyError::ValidationError(
-            "Invalid values in star poll.".to_owned(),
+        return Result::Err(errors::MyError::invalid(
+            "invalid_poll",
+            "polls.star",
+            "Invalid values in star poll.",
         ));
     }
 
-    let positive_count = vote.polls.star.iter().copied().filter(|v| *v > 0).count();
+    let positive_count = star.iter().copied().filter(|v| *v > 0).count();
 
     if positive_count == 0 {
-        return Result::Err(errors::MyError::ValidationError(
-            "Invalid values in star poll - no positive vote".to_owned(),
+        return Result::Err(errors::MyError::invalid(
+            "invalid_poll",
+            "polls.star",
+            "Invalid values in star poll - no positive vote.",
         ));
     }
 
-    return Result::Ok(());
+    Result::Ok(())
+}
+
+const TEXT_PRESENTATION_SELECTOR: char = '\u{FE0E}';
+
+/// Canonical form of a single emoji: fully qualified and without a skin tone. Empty input stays
+/// empty, anything else than one emoji is rejected.
+fn normalize_emoji(value: &str) -> Option<String> {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        return Some(String::new());
+    }
+    if trimmed.graphemes(true).count() != 1 {
+        return None;
+    }
+    let without_selector: String = trimmed
+        .chars()
+        .filter(|&c| c != TEXT_PRESENTATION_SELECTOR)
+        .collect();
+    let emoji = emojis::get(&without_selector)?;
+    let emoji = emoji.with_skin_tone(SkinTone::Default).unwrap_or(emoji);
+    Some(emoji.as_str().to_owned())
 }
 
-pub fn validate_vote(vote: &models::VoteWeb) -> Result<(), errors::MyError> {
+fn validate_emoji_poll(emoji: &[String], candidate_count: i32) -> Result<(), errors::MyError> {
+    if emoji.len() as i32 != candidate_count {
+        return Result::Err(errors::MyError::invalid(
+            "invalid_poll",
+            "polls.emoji",
+            "Invalid length of emoji poll array.",
+        ));
+    }
+
+    if let Some(index) = emoji.iter().position(|v| normalize_emoji(v).is_none()) {
+        return Result::Err(errors::MyError::invalid(
+            "invalid_poll",
+            &format!("polls.emoji[{}]", index),
+            "Invalid value in emoji poll - only a single emoji is allowed.",
+        ));
+    }
+
+    Result::Ok(())
+}
+
+/// Rewrites answers of a validated vote to their canonical form.
+pub fn normalize_polls(polls: &mut models::PollsWeb) {
+    for value in polls.emoji.iter_mut().flatten() {
+        if let Some(normalized) = normalize_emoji(value) {
+            *value = normalized;
+        }
+    }
+}
+
+/// Path of the poll in a submitted vote, e.g. `polls.twoRound`.
+fn poll_field(poll_type: models::PollType) -> String {
+    let name = serde_json::to_value(poll_type).unwrap();
+    format!("polls.{}", name.as_str().unwrap_or_default())
+}
+
+/// Checks that the vote answers the poll if and only if the election enables it.
+fn validate_poll<T: ?Sized>(
+    election: &models::ElectionWeb,
+    poll_type: models::PollType,
+    value: Option<&T>,
+    validator: impl Fn(&T) -> Result<(), errors::MyError>,
+) -> Result<(), errors::MyError> {
+    match (election.has_poll(poll_type), value) {
+        (true, Some(value)) => validator(value),
+        (true, None) => Result::Err(errors::MyError::invalid(
+            "missing_poll",
+            &poll_field(poll_type),
+            &format!("Missing {} poll.", poll_type),
+        )),
+        (false, Some(_)) => Result::Err(errors::MyError::invalid(
+            "poll_not_enabled",
+            &poll_field(poll_type),
+            &format!("The {} poll is not enabled in this election.", poll_type),
+        )),
+        (false, None) => Result::Ok(()),
+    }
+}
+
+pub fn validate_vote(
+    vote: &models::VoteWeb,
+    election: &models::ElectionWeb,
+    difficulty: &models::DifficultyWeb,
+    challenges: &ChallengeStore,
+) -> Result<(), errors::MyError> {
     validate_uuid(vote)?;
-    validate_nonces(vote)?;
-    validate_order(vote)?;
-    validate_two_round_poll(vote)?;
-    validate_one_round_poll(vote)?;
-    validate_divide_poll(vote)?;
-    validate_d21_poll(vote)?;
-    validate_doodle_poll(vote)?;
-    validate_order_poll(vote)?;
-    validate_star_poll(vote)?;
-    return Result::Ok(());
+    validate_ballot(&vote.order, &vote.polls, election)?;
+    validate_nonces(vote, election, difficulty, challenges)
+}
+
+/// Validates the display order and poll answers, which are replaced when a vote is edited.
+pub fn validate_ballot(
+    order: &[i32],
+    polls: &models::PollsWeb,
+    election: &models::ElectionWeb,
+) -> Result<(), errors::MyError> {
+    use models::PollType;
+
+    let candidate_count = election.candidates.len() as i32;
+
+    validate_order(order, candidate_count)?;
+    validate_poll(
+        election,
+        PollType::TwoRound,
+        polls.two_round.as_ref(),
+        |&v| validate_two_round_poll(v, candidate_count),
+    )?;
+    validate_poll(
+        election,
+        PollType::OneRound,
+        polls.one_round.as_ref(),
+        |&v| validate_one_round_poll(v, candidate_count),
+    )?;
+    validate_poll(election, PollType::Divide, polls.divide.as_deref(), |v| {
+        validate_divide_poll(v, candidate_count)
+    })?;
+    validate_poll(election, PollType::D21, polls.d21.as_deref(), |v| {
+        validate_d21_poll(v, candidate_count)
+    })?;
+    validate_poll(election, PollType::Doodle, polls.doodle.as_deref(), |v| {
+        validate_doodle_poll(v, candidate_count)
+    })?;
+    validate_poll(election, PollType::Order, polls.order.as_deref(), |v| {
+        validate_order_poll(v, candidate_count)
+    })?;
+    validate_poll(election, PollType::Star, polls.star.as_deref(), |v| {
+        validate_star_poll(v, candidate_count)
+    })?;
+    validate_poll(election, PollType::Emoji, polls.emoji.as_deref(), |v| {
+        validate_emoji_poll(v, candidate_count)
+    })?;
+    Result::Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_emoji_removes_skin_tone() {
+        assert_eq!(normalize_emoji("👍🏽").as_deref(), Some("👍"));
+        assert_eq!(normalize_emoji("🧑🏿‍🚀").as_deref(), Some("🧑‍🚀"));
+    }
+
+    #[test]
+    fn normalize_emoji_handles_presentation_selectors() {
+        assert_eq!(normalize_emoji("❤\u{FE0E}").as_deref(), Some("❤️"));
+        assert_eq!(normalize_emoji("❤\u{FE0F}").as_deref(), Some("❤️"));
+        assert_eq!(normalize_emoji("❤").as_deref(), Some("❤️"));
+    }
+
+    #[test]
+    fn normalize_emoji_rejects_more_than_one_emoji() {
+        assert_eq!(normalize_emoji("👍👍"), None);
+        assert_eq!(normalize_emoji("👍 x"), None);
+        assert_eq!(normalize_emoji("x"), None);
+        assert_eq!(normalize_emoji("👨‍👩‍👧").as_deref(), Some("👨‍👩‍👧"));
+    }
+
+    #[test]
+    fn normalize_emoji_keeps_empty_answers_empty() {
+        assert_eq!(normalize_emoji("").as_deref(), Some(""));
+        assert_eq!(normalize_emoji(" \t ").as_deref(), Some(""));
+        assert_eq!(normalize_emoji(" 👍 ").as_deref(), Some("👍"));
+    }
+
+    #[test]
+    fn validate_emoji_poll_reports_the_invalid_answer() {
+        let emoji = vec!["👍".to_owned(), "".to_owned(), "ab".to_owned()];
+
+        match validate_emoji_poll(&emoji, 3) {
+            Result::Err(errors::MyError::ValidationError(input)) => {
+                assert_eq!(input.code, "invalid_poll");
+                assert_eq!(input.field.as_deref(), Some("polls.emoji[2]"));
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert!(validate_emoji_poll(&emoji[..2], 2).is_ok());
+    }
 }